@@ -50,6 +50,20 @@ where
     }
 }
 
+impl<T> AddAssign<&AccumulateTensors<T>> for AccumulateTensors<T>
+where
+    T: Clone + Default + for<'a> AddAssign<&'a T> + 'static,
+{
+    fn add_assign(&mut self, other: &AccumulateTensors<T>) {
+        self.extend(
+            other
+                .table
+                .iter()
+                .map(|(&slot, tensors)| (slot, tensors.clone())),
+        );
+    }
+}
+
 impl<T> Default for AccumulateTensors<T> {
     fn default() -> Self {
         Self {
@@ -0,0 +1,25 @@
+use deep::{DeepError, OpTy};
+use failure::Fail;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "input not provided for \"{}\"", name)]
+    InputNotProvided { name: String },
+    #[fail(
+        display = "internal node \"{}\" (\"{:?}\") was not found in the feed dict (not computed)",
+        node, ty
+    )]
+    InternalNotComputed { node: usize, ty: Option<OpTy> },
+    #[fail(display = "no handler for \"{:?}\"", ty)]
+    OpHasNoHandler { ty: OpTy },
+    #[fail(display = "unable to save or load the state: {}", reason)]
+    State { reason: String },
+    #[fail(display = "invalid graph: {}", error)]
+    Graph { error: DeepError },
+}
+
+impl From<DeepError> for Error {
+    fn from(error: DeepError) -> Self {
+        Error::Graph { error }
+    }
+}
@@ -1,24 +1,14 @@
 mod accumulate_tensors;
+// The `Fail` derive from `failure` generates its impls inside an anonymous const.
+#[allow(non_local_definitions)]
+mod error;
 
 pub use accumulate_tensors::AccumulateTensors;
+pub use error::Error;
 
 use deep::*;
-use failure::Fail;
 use std::collections::{hash_map::Entry, HashMap};
 
-#[derive(Debug, Fail)]
-pub enum Error {
-    #[fail(display = "input not provided for \"{}\"", name)]
-    InputNotProvided { name: String },
-    #[fail(
-        display = "internal node \"{}\" (\"{:?}\") was not found in the feed dict (not computed)",
-        node, ty
-    )]
-    InternalNotComputed { node: usize, ty: Option<OpTy> },
-    #[fail(display = "no handler for \"{:?}\"", ty)]
-    OpHasNoHandler { ty: OpTy },
-}
-
 pub type Result<T> = std::result::Result<T, Error>;
 type SResult<T, E> = std::result::Result<T, E>;
 
@@ -60,6 +50,10 @@ pub trait Propogate: Backend {
 
 pub struct Tape<B: Backend> {
     solved: HashMap<Internal, Vec<B::Tensor>>,
+    fed: HashMap<String, B::Tensor>,
+    /// The seed stochastic ops like [Op::Dropout] derive their randomness from, or `None`
+    /// outside of training.
+    seed: Option<u64>,
 }
 
 impl<B, T> Default for Tape<B>
@@ -70,6 +64,8 @@ where
     fn default() -> Self {
         Self {
             solved: Default::default(),
+            fed: Default::default(),
+            seed: None,
         }
     }
 }
@@ -83,6 +79,41 @@ where
         Self::default()
     }
 
+    /// Creates a tape for training, where stochastic ops like [Op::Dropout] are active and get
+    /// their randomness from `seed`.
+    pub fn training(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the tape is for training, see [Tape::training].
+    pub fn is_training(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// The seed of the randomness of the stochastic op at `node`, which is different for every
+    /// node but always the same for one node, so the backward pass can reproduce it.
+    ///
+    /// Returns `None` if the tape isn't for training.
+    pub fn mask_seed(&self, node: usize) -> Option<u64> {
+        self.seed
+            .map(|seed| seed ^ (node as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    /// The outputs of every op solved so far.
+    ///
+    /// Ops are keyed by their node with an output of `0`, the value holding all of their outputs.
+    pub fn solved(&self) -> &HashMap<Internal, Vec<B::Tensor>> {
+        &self.solved
+    }
+
+    /// The tensors fetched from the feed dict while solving, by name.
+    pub fn fed(&self) -> &HashMap<String, B::Tensor> {
+        &self.fed
+    }
+
     pub fn input(
         &self,
         backend: &B,
@@ -96,15 +127,18 @@ where
         match input {
             Input::Feed(name) => backend
                 .feed(inputs, &name)
-                .ok_or_else(|| Error::InputNotProvided { name }),
+                .ok_or(Error::InputNotProvided { name }),
             Input::Internal(internal) => self
                 .solved
-                .get(&internal)
+                .get(&Internal {
+                    output: 0,
+                    ..internal
+                })
                 .and_then(|v| v.get(internal.output))
                 .cloned()
                 .ok_or_else(|| Error::InternalNotComputed {
                     node: internal.node,
-                    ty: graph.ops.get(internal.node).map(|op| op.into()),
+                    ty: graph.ops().get(internal.node).map(|op| op.into()),
                 }),
         }
     }
@@ -121,25 +155,36 @@ where
         B: Immediate + Feed,
     {
         match input {
-            Input::Feed(name) => backend
-                .feed(inputs, &name)
-                .ok_or_else(|| Error::InputNotProvided { name }),
+            Input::Feed(name) => {
+                let tensor = backend
+                    .feed(inputs, &name)
+                    .ok_or_else(|| Error::InputNotProvided { name: name.clone() })?;
+                self.fed.insert(name, tensor.clone());
+                Ok(tensor)
+            }
             Input::Internal(internal) => {
-                let op = match self.solved.entry(internal) {
+                // All the outputs of an op are solved at once, so they are stored under output `0`.
+                let key = Internal {
+                    output: 0,
+                    ..internal
+                };
+                let op = match self.solved.entry(key) {
                     Entry::Occupied(o) => return Ok(o.get()[internal.output].clone()),
-                    Entry::Vacant(_) => graph.ops[internal.node].clone(),
+                    Entry::Vacant(_) => graph.ops()[internal.node].clone(),
                 };
                 let ty = (&op).into();
-                ImOp::solve(op, self, backend, graph, state, inputs).and_then(|imop| {
-                    backend
-                        .solve(imop, &state[internal.node][..])
-                        .map(|solutions| {
-                            let output = solutions[internal.output].clone();
-                            self.solved.insert(internal, solutions);
-                            output
-                        })
-                        .ok_or_else(|| Error::OpHasNoHandler { ty })
-                })
+                ImOp::solve(op, internal.node, self, backend, graph, state, inputs).and_then(
+                    |imop| {
+                        backend
+                            .solve(imop, &state[internal.node][..])
+                            .map(|solutions| {
+                                let output = solutions[internal.output].clone();
+                                self.solved.insert(key, solutions);
+                                output
+                            })
+                            .ok_or(Error::OpHasNoHandler { ty })
+                    },
+                )
             }
         }
     }
@@ -150,6 +195,7 @@ where
     /// This process will produce the `Backend::Delta` that can be used to train the state.
     ///
     /// This delta is accumulated in the `deltas` parameter utilising its `Extend` impl.
+    #[allow(clippy::too_many_arguments)]
     pub fn backprop<E>(
         &self,
         backend: &B,
@@ -168,7 +214,7 @@ where
             Input::Feed(_) => Ok(deltas),
             Input::Internal(internal) => {
                 let op = graph
-                    .ops
+                    .ops()
                     .get(internal.node)
                     .expect("node requested in backprop but does not exist");
                 ImOp::backprop(
@@ -191,8 +237,57 @@ where
 pub enum ImOp<B: Backend + ?Sized> {
     Add(B::Tensor, B::Tensor),
     Sub(B::Tensor, B::Tensor),
+    Mul(B::Tensor, B::Tensor),
+    Div(B::Tensor, B::Tensor),
     Square(B::Tensor),
+    Neg(B::Tensor),
+    Exp(B::Tensor),
+    Log(B::Tensor),
+    Sqrt(B::Tensor),
+    Tanh(B::Tensor),
+    Sigmoid(B::Tensor),
+    Relu(B::Tensor),
+    ReluThreshold(B::Tensor, f64),
+    Pow(B::Tensor, f64),
+    Max(B::Tensor, B::Tensor),
+    Min(B::Tensor, B::Tensor),
+    Sum(B::Tensor),
+    Mean(B::Tensor),
+    SumAxis(B::Tensor, usize),
+    Reshape(B::Tensor, Vec<usize>),
+    Transpose(B::Tensor, usize, usize),
+    MatMul(B::Tensor, B::Tensor),
+    Const(Vec<usize>, f64),
+    SplitAt(B::Tensor, usize),
+    Clamp(B::Tensor, f64, f64),
+    Abs(B::Tensor),
+    Softmax(B::Tensor, usize),
+    LogSoftmax(B::Tensor, usize),
+    Broadcast(B::Tensor, Vec<usize>),
+    Concat(Vec<B::Tensor>, usize),
+    Stack(Vec<B::Tensor>, usize),
+    Slice(B::Tensor, Vec<(usize, usize)>),
     TrainConst,
+    /// The seed of the dropout mask, or `None` outside of training.
+    Dropout(B::Tensor, f64, Option<u64>),
+    Reciprocal(B::Tensor),
+    Sin(B::Tensor),
+    Cos(B::Tensor),
+    Pad(B::Tensor, Vec<(usize, usize)>, f64),
+    /// The input, the kernel, the stride and the padding.
+    Conv2d(B::Tensor, B::Tensor, (usize, usize), (usize, usize)),
+    /// The input, the kernel size and the stride.
+    MaxPool2d(B::Tensor, (usize, usize), (usize, usize)),
+    /// The table and the indices.
+    Embedding(B::Tensor, B::Tensor),
+    /// The input, gamma, beta and whether the op is training.
+    BatchNorm(B::Tensor, B::Tensor, B::Tensor, bool),
+    /// The input, gamma, beta and eps.
+    LayerNorm(B::Tensor, B::Tensor, B::Tensor, f64),
+    Gelu(B::Tensor),
+    LeakyRelu(B::Tensor, f64),
+    SquaredDiff(B::Tensor, B::Tensor),
+    TrainInit,
 }
 
 impl<B> ImOp<B>
@@ -215,6 +310,22 @@ where
         }
     }
 
+    pub fn mul(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::Mul(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn div(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::Div(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
+
     pub fn square(self) -> SResult<B::Tensor, Self> {
         if let ImOp::Square(a) = self {
             Ok(a)
@@ -222,6 +333,325 @@ where
             Err(self)
         }
     }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn neg(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Neg(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn exp(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Exp(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn log(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Log(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn sqrt(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Sqrt(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn tanh(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Tanh(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn sigmoid(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Sigmoid(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn relu(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Relu(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn relu_threshold(self) -> SResult<(B::Tensor, f64), Self> {
+        if let ImOp::ReluThreshold(a, threshold) = self {
+            Ok((a, threshold))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn pow(self) -> SResult<(B::Tensor, f64), Self> {
+        if let ImOp::Pow(a, exponent) = self {
+            Ok((a, exponent))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn max(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::Max(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn min(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::Min(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn sum(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Sum(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn mean(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Mean(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn sum_axis(self) -> SResult<(B::Tensor, usize), Self> {
+        if let ImOp::SumAxis(a, axis) = self {
+            Ok((a, axis))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn reshape(self) -> SResult<(B::Tensor, Vec<usize>), Self> {
+        if let ImOp::Reshape(a, shape) = self {
+            Ok((a, shape))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn transpose(self) -> SResult<(B::Tensor, usize, usize), Self> {
+        if let ImOp::Transpose(a, axis_a, axis_b) = self {
+            Ok((a, axis_a, axis_b))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn matmul(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::MatMul(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn split_at(self) -> SResult<(B::Tensor, usize), Self> {
+        if let ImOp::SplitAt(a, index) = self {
+            Ok((a, index))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn clamp(self) -> SResult<(B::Tensor, f64, f64), Self> {
+        if let ImOp::Clamp(a, min, max) = self {
+            Ok((a, min, max))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn abs(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Abs(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn softmax(self) -> SResult<(B::Tensor, usize), Self> {
+        if let ImOp::Softmax(a, axis) = self {
+            Ok((a, axis))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn log_softmax(self) -> SResult<(B::Tensor, usize), Self> {
+        if let ImOp::LogSoftmax(a, axis) = self {
+            Ok((a, axis))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn broadcast(self) -> SResult<(B::Tensor, Vec<usize>), Self> {
+        if let ImOp::Broadcast(a, shape) = self {
+            Ok((a, shape))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn concat(self) -> SResult<(Vec<B::Tensor>, usize), Self> {
+        if let ImOp::Concat(tensors, axis) = self {
+            Ok((tensors, axis))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn stack(self) -> SResult<(Vec<B::Tensor>, usize), Self> {
+        if let ImOp::Stack(tensors, axis) = self {
+            Ok((tensors, axis))
+        } else {
+            Err(self)
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn slice(self) -> SResult<(B::Tensor, Vec<(usize, usize)>), Self> {
+        if let ImOp::Slice(a, ranges) = self {
+            Ok((a, ranges))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn dropout(self) -> SResult<(B::Tensor, f64, Option<u64>), Self> {
+        if let ImOp::Dropout(a, p, seed) = self {
+            Ok((a, p, seed))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn reciprocal(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Reciprocal(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn sin(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Sin(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn cos(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Cos(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn pad(self) -> SResult<(B::Tensor, Vec<(usize, usize)>, f64), Self> {
+        if let ImOp::Pad(a, padding, value) = self {
+            Ok((a, padding, value))
+        } else {
+            Err(self)
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn conv2d(self) -> SResult<(B::Tensor, B::Tensor, (usize, usize), (usize, usize)), Self> {
+        if let ImOp::Conv2d(a, b, stride, padding) = self {
+            Ok((a, b, stride, padding))
+        } else {
+            Err(self)
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn max_pool2d(self) -> SResult<(B::Tensor, (usize, usize), (usize, usize)), Self> {
+        if let ImOp::MaxPool2d(a, kernel, stride) = self {
+            Ok((a, kernel, stride))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn embedding(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::Embedding(table, indices) = self {
+            Ok((table, indices))
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Decomposes into the input, gamma and beta, in that order.
+    pub fn batch_norm(self) -> SResult<Vec<B::Tensor>, Self> {
+        if let ImOp::BatchNorm(input, gamma, beta, _) = self {
+            Ok(vec![input, gamma, beta])
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Decomposes into the input, gamma and beta, in that order.
+    pub fn layer_norm(self) -> SResult<Vec<B::Tensor>, Self> {
+        if let ImOp::LayerNorm(input, gamma, beta, _) = self {
+            Ok(vec![input, gamma, beta])
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn gelu(self) -> SResult<B::Tensor, Self> {
+        if let ImOp::Gelu(a) = self {
+            Ok(a)
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn leaky_relu(self) -> SResult<(B::Tensor, f64), Self> {
+        if let ImOp::LeakyRelu(a, slope) = self {
+            Ok((a, slope))
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn squared_diff(self) -> SResult<(B::Tensor, B::Tensor), Self> {
+        if let ImOp::SquaredDiff(a, b) = self {
+            Ok((a, b))
+        } else {
+            Err(self)
+        }
+    }
 }
 
 impl<B, T> ImOp<B>
@@ -229,9 +659,10 @@ where
     B: Backend<Tensor = T>,
     T: Clone,
 {
-    fn solve<'a>(
+    fn solve(
         op: Op,
-        tape: &'a mut Tape<B>,
+        node: usize,
+        tape: &mut Tape<B>,
         backend: &B,
         graph: &Graph,
         state: &[Vec<B::Tensor>],
@@ -240,6 +671,8 @@ where
     where
         B: Feed + Immediate,
     {
+        let mask_seed = tape.mask_seed(node);
+        let training = tape.is_training();
         let mut tensor = |input| tape.solve(backend, graph, state, inputs, input);
         let mut double = |a, b, f: fn(B::Tensor, B::Tensor) -> Self| {
             tensor(a).and_then(|a| tensor(b).map(|b| f(a, b)))
@@ -247,13 +680,88 @@ where
         match op {
             Op::Add(a, b) => double(a, b, ImOp::Add),
             Op::Sub(a, b) => double(a, b, ImOp::Sub),
+            Op::Mul(a, b) => double(a, b, ImOp::Mul),
+            Op::Div(a, b) => double(a, b, ImOp::Div),
             Op::Square(a) => tensor(a).map(ImOp::Square),
+            Op::Neg(a) => tensor(a).map(ImOp::Neg),
+            Op::Exp(a) => tensor(a).map(ImOp::Exp),
+            Op::Log(a) => tensor(a).map(ImOp::Log),
+            Op::Sqrt(a) => tensor(a).map(ImOp::Sqrt),
+            Op::Tanh(a) => tensor(a).map(ImOp::Tanh),
+            Op::Sigmoid(a) => tensor(a).map(ImOp::Sigmoid),
+            Op::Relu(a) => tensor(a).map(ImOp::Relu),
+            Op::ReluThreshold(a, threshold) => tensor(a).map(|a| ImOp::ReluThreshold(a, threshold)),
+            Op::Pow(a, exponent) => tensor(a).map(|a| ImOp::Pow(a, exponent)),
+            Op::Max(a, b) => double(a, b, ImOp::Max),
+            Op::Min(a, b) => double(a, b, ImOp::Min),
+            Op::Sum(a) => tensor(a).map(ImOp::Sum),
+            Op::Mean(a) => tensor(a).map(ImOp::Mean),
+            Op::SumAxis(a, axis) => tensor(a).map(|a| ImOp::SumAxis(a, axis)),
+            Op::Reshape(a, shape) => tensor(a).map(|a| ImOp::Reshape(a, shape)),
+            Op::Transpose(a, axis_a, axis_b) => {
+                tensor(a).map(|a| ImOp::Transpose(a, axis_a, axis_b))
+            }
+            Op::MatMul(a, b) => double(a, b, ImOp::MatMul),
+            Op::Const(shape, value) => Ok(ImOp::Const(shape, value)),
+            Op::SplitAt(a, index) => tensor(a).map(|a| ImOp::SplitAt(a, index)),
+            Op::Clamp(a, min, max) => tensor(a).map(|a| ImOp::Clamp(a, min, max)),
+            Op::Abs(a) => tensor(a).map(ImOp::Abs),
+            Op::Softmax(a, axis) => tensor(a).map(|a| ImOp::Softmax(a, axis)),
+            Op::LogSoftmax(a, axis) => tensor(a).map(|a| ImOp::LogSoftmax(a, axis)),
+            Op::Broadcast(a, shape) => tensor(a).map(|a| ImOp::Broadcast(a, shape)),
+            Op::Concat(inputs, axis) => inputs
+                .into_iter()
+                .map(&mut tensor)
+                .collect::<Result<_>>()
+                .map(|tensors| ImOp::Concat(tensors, axis)),
+            Op::Stack(inputs, axis) => inputs
+                .into_iter()
+                .map(&mut tensor)
+                .collect::<Result<_>>()
+                .map(|tensors| ImOp::Stack(tensors, axis)),
+            Op::Slice(a, ranges) => tensor(a).map(|a| ImOp::Slice(a, ranges)),
+            Op::Dropout(a, p) => tensor(a).map(|a| ImOp::Dropout(a, p, mask_seed)),
+            Op::Reciprocal(a) => tensor(a).map(ImOp::Reciprocal),
+            Op::Sin(a) => tensor(a).map(ImOp::Sin),
+            Op::Cos(a) => tensor(a).map(ImOp::Cos),
+            Op::Pad(a, padding, value) => tensor(a).map(|a| ImOp::Pad(a, padding, value)),
+            Op::Conv2d {
+                input,
+                kernel,
+                stride,
+                padding,
+            } => tensor(input)
+                .and_then(|a| tensor(kernel).map(|b| ImOp::Conv2d(a, b, stride, padding))),
+            Op::MaxPool2d {
+                input,
+                kernel,
+                stride,
+            } => tensor(input).map(|a| ImOp::MaxPool2d(a, kernel, stride)),
+            Op::Embedding { table, indices } => double(table, indices, ImOp::Embedding),
+            Op::BatchNorm { input, gamma, beta } => tensor(input).and_then(|input| {
+                let gamma = tensor(gamma)?;
+                tensor(beta).map(|beta| ImOp::BatchNorm(input, gamma, beta, training))
+            }),
+            Op::LayerNorm {
+                input,
+                gamma,
+                beta,
+                eps,
+            } => tensor(input).and_then(|input| {
+                let gamma = tensor(gamma)?;
+                tensor(beta).map(|beta| ImOp::LayerNorm(input, gamma, beta, eps))
+            }),
+            Op::Gelu(a) => tensor(a).map(ImOp::Gelu),
+            Op::LeakyRelu(a, slope) => tensor(a).map(|a| ImOp::LeakyRelu(a, slope)),
+            Op::SquaredDiff(a, b) => double(a, b, ImOp::SquaredDiff),
             Op::TrainConst(..) => Ok(ImOp::TrainConst),
+            Op::TrainInit(..) => Ok(ImOp::TrainInit),
         }
     }
 
     /// This takes the output delta of a particular output from the op and propogates it backwards to the inputs.
-    fn backprop<'a, E>(
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    fn backprop<E>(
         op: Op,
         internal: Internal,
         tape: &Tape<B>,
@@ -286,12 +794,13 @@ where
                         .as_slice(),
                     (internal.output, output_delta),
                 )
-                .ok_or_else(|| Error::OpHasNoHandler { ty })
+                .ok_or(Error::OpHasNoHandler { ty })
         };
 
         // This is to appease the borrow checker because I was getting moved closure errors.
         let gradients1 = gradients.clone();
         let gradients2 = gradients.clone();
+        let gradients3 = gradients.clone();
 
         // This recursively backprops to send the gradient to a new graph node.
         let backprop = |input, output_delta, tape: &Tape<B>, deltas| {
@@ -304,7 +813,7 @@ where
         // ImOp into a tuple tensors to pass the gradient backwards.
         let binary = |ia: Input,
                       ib: Input,
-                      fimop: fn(B::Tensor, B::Tensor) -> Self,
+                      fimop: &dyn Fn(B::Tensor, B::Tensor) -> Self,
                       fundo: fn(ImOp<B>) -> SResult<(B::Tensor, B::Tensor), Self>,
                       mut deltas: E| {
             tensor(ia.clone(), tape)
@@ -331,7 +840,7 @@ where
         // This requires the input, a function to turn the input into an ImOp, and a function to decompose the
         // ImOp into its tensor to pass the gradient backwards.
         let unary = |ia: Input,
-                     fimop: fn(B::Tensor) -> Self,
+                     fimop: &dyn Fn(B::Tensor) -> Self,
                      fundo: fn(ImOp<B>) -> SResult<B::Tensor, Self>,
                      mut deltas: E| {
             tensor(ia.clone(), tape)
@@ -350,8 +859,39 @@ where
                 .and_then(|ta| backprop(ia, ta, tape, deltas))
         };
 
+        // This performs the backprop for an op with any number of parameters, like unary does
+        // for one.
+        let variadic = |ias: Vec<Input>,
+                        fimop: &dyn Fn(Vec<B::Tensor>) -> Self,
+                        fundo: fn(ImOp<B>) -> SResult<Vec<B::Tensor>, Self>,
+                        mut deltas: E| {
+            ias.iter()
+                .map(|ia| tensor(ia.clone(), tape))
+                .collect::<Result<Vec<_>>>()
+                .map(fimop)
+                .and_then(gradients3)
+                .map(|(input_gradients, train_gradients)| {
+                    deltas.extend(std::iter::once((internal.node, train_gradients)));
+                    input_gradients
+                })
+                .map(|imop| {
+                    fundo(imop).unwrap_or_else(|imop| {
+                        let imop_ty: OpTy = (&imop).into();
+                        panic!("op \"{:?}\" gave back ImOp type \"{:?}\"", ty, imop_ty);
+                    })
+                })
+                .and_then(|tas| {
+                    ias.into_iter()
+                        .zip(tas)
+                        .try_fold(deltas, |deltas, (ia, ta)| backprop(ia, ta, tape, deltas))
+                })
+        };
+
         // This updates the delta for this op only. It has no runtime inputs, so it does not recurse.
         let nullary = |imop: Self, mut deltas: E| {
+            if graph.is_frozen(internal.node) {
+                return Ok(deltas);
+            }
             gradients(imop).map(|(_, train_gradients)| {
                 deltas.extend(std::iter::once((internal.node, train_gradients)));
                 deltas
@@ -359,10 +899,179 @@ where
         };
 
         match op {
-            Op::Add(a, b) => binary(a, b, ImOp::Add, ImOp::add, deltas),
-            Op::Sub(a, b) => binary(a, b, ImOp::Sub, ImOp::sub, deltas),
-            Op::Square(a) => unary(a, ImOp::Square, ImOp::square, deltas),
+            Op::Add(a, b) => binary(a, b, &ImOp::Add, ImOp::add, deltas),
+            Op::Sub(a, b) => binary(a, b, &ImOp::Sub, ImOp::sub, deltas),
+            Op::Mul(a, b) => binary(a, b, &ImOp::Mul, ImOp::mul, deltas),
+            Op::Div(a, b) => binary(a, b, &ImOp::Div, ImOp::div, deltas),
+            Op::Square(a) => unary(a, &ImOp::Square, ImOp::square, deltas),
+            Op::Neg(a) => unary(a, &ImOp::Neg, ImOp::neg, deltas),
+            Op::Exp(a) => unary(a, &ImOp::Exp, ImOp::exp, deltas),
+            Op::Log(a) => unary(a, &ImOp::Log, ImOp::log, deltas),
+            Op::Sqrt(a) => unary(a, &ImOp::Sqrt, ImOp::sqrt, deltas),
+            Op::Tanh(a) => unary(a, &ImOp::Tanh, ImOp::tanh, deltas),
+            Op::Sigmoid(a) => unary(a, &ImOp::Sigmoid, ImOp::sigmoid, deltas),
+            Op::Relu(a) => unary(a, &ImOp::Relu, ImOp::relu, deltas),
+            Op::ReluThreshold(a, threshold) => unary(
+                a,
+                &|a| ImOp::ReluThreshold(a, threshold),
+                |imop| imop.relu_threshold().map(|(a, _)| a),
+                deltas,
+            ),
+            Op::Pow(a, exponent) => unary(
+                a,
+                &|a| ImOp::Pow(a, exponent),
+                |imop| imop.pow().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Max(a, b) => binary(a, b, &ImOp::Max, ImOp::max, deltas),
+            Op::Min(a, b) => binary(a, b, &ImOp::Min, ImOp::min, deltas),
+            Op::Sum(a) => unary(a, &ImOp::Sum, ImOp::sum, deltas),
+            Op::Mean(a) => unary(a, &ImOp::Mean, ImOp::mean, deltas),
+            Op::SumAxis(a, axis) => unary(
+                a,
+                &|a| ImOp::SumAxis(a, axis),
+                |imop| imop.sum_axis().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Reshape(a, shape) => unary(
+                a,
+                &|a| ImOp::Reshape(a, shape.clone()),
+                |imop| imop.reshape().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Transpose(a, axis_a, axis_b) => unary(
+                a,
+                &|a| ImOp::Transpose(a, axis_a, axis_b),
+                |imop| imop.transpose().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::MatMul(a, b) => binary(a, b, &ImOp::MatMul, ImOp::matmul, deltas),
+            // Constants aren't trainable and have no inputs, so there is nothing to propogate.
+            Op::Const(..) => Ok(deltas),
+            Op::SplitAt(a, index) => unary(
+                a,
+                &|a| ImOp::SplitAt(a, index),
+                |imop| imop.split_at().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Clamp(a, min, max) => unary(
+                a,
+                &|a| ImOp::Clamp(a, min, max),
+                |imop| imop.clamp().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Abs(a) => unary(a, &ImOp::Abs, ImOp::abs, deltas),
+            Op::Softmax(a, axis) => unary(
+                a,
+                &|a| ImOp::Softmax(a, axis),
+                |imop| imop.softmax().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::LogSoftmax(a, axis) => unary(
+                a,
+                &|a| ImOp::LogSoftmax(a, axis),
+                |imop| imop.log_softmax().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Broadcast(a, shape) => unary(
+                a,
+                &|a| ImOp::Broadcast(a, shape.clone()),
+                |imop| imop.broadcast().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Concat(inputs, axis) => variadic(
+                inputs,
+                &|tensors| ImOp::Concat(tensors, axis),
+                |imop| imop.concat().map(|(tensors, _)| tensors),
+                deltas,
+            ),
+            Op::Stack(inputs, axis) => variadic(
+                inputs,
+                &|tensors| ImOp::Stack(tensors, axis),
+                |imop| imop.stack().map(|(tensors, _)| tensors),
+                deltas,
+            ),
+            Op::Slice(a, ranges) => unary(
+                a,
+                &|a| ImOp::Slice(a, ranges.clone()),
+                |imop| imop.slice().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Dropout(a, p) => unary(
+                a,
+                &|a| ImOp::Dropout(a, p, tape.mask_seed(internal.node)),
+                |imop| imop.dropout().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Reciprocal(a) => unary(a, &ImOp::Reciprocal, ImOp::reciprocal, deltas),
+            Op::Sin(a) => unary(a, &ImOp::Sin, ImOp::sin, deltas),
+            Op::Cos(a) => unary(a, &ImOp::Cos, ImOp::cos, deltas),
+            Op::Pad(a, padding, value) => unary(
+                a,
+                &|a| ImOp::Pad(a, padding.clone(), value),
+                |imop| imop.pad().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Conv2d {
+                input,
+                kernel,
+                stride,
+                padding,
+            } => binary(
+                input,
+                kernel,
+                &|a, b| ImOp::Conv2d(a, b, stride, padding),
+                |imop| imop.conv2d().map(|(a, b, ..)| (a, b)),
+                deltas,
+            ),
+            Op::MaxPool2d {
+                input,
+                kernel,
+                stride,
+            } => unary(
+                input,
+                &|a| ImOp::MaxPool2d(a, kernel, stride),
+                |imop| imop.max_pool2d().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::Embedding { table, indices } => {
+                binary(table, indices, &ImOp::Embedding, ImOp::embedding, deltas)
+            }
+            Op::BatchNorm { input, gamma, beta } => variadic(
+                vec![input, gamma, beta],
+                &|tensors| {
+                    let mut tensors = tensors.into_iter();
+                    let mut next = || tensors.next().unwrap();
+                    ImOp::BatchNorm(next(), next(), next(), tape.is_training())
+                },
+                ImOp::batch_norm,
+                deltas,
+            ),
+            Op::LayerNorm {
+                input,
+                gamma,
+                beta,
+                eps,
+            } => variadic(
+                vec![input, gamma, beta],
+                &|tensors| {
+                    let mut tensors = tensors.into_iter();
+                    let mut next = || tensors.next().unwrap();
+                    ImOp::LayerNorm(next(), next(), next(), eps)
+                },
+                ImOp::layer_norm,
+                deltas,
+            ),
+            Op::Gelu(a) => unary(a, &ImOp::Gelu, ImOp::gelu, deltas),
+            Op::LeakyRelu(a, slope) => unary(
+                a,
+                &|a| ImOp::LeakyRelu(a, slope),
+                |imop| imop.leaky_relu().map(|(a, ..)| a),
+                deltas,
+            ),
+            Op::SquaredDiff(a, b) => binary(a, b, &ImOp::SquaredDiff, ImOp::squared_diff, deltas),
             Op::TrainConst(..) => nullary(ImOp::TrainConst, deltas),
+            Op::TrainInit(..) => nullary(ImOp::TrainInit, deltas),
         }
     }
 }
@@ -375,8 +1084,51 @@ where
         match imop {
             ImOp::Add(..) => OpTy::Add,
             ImOp::Sub(..) => OpTy::Sub,
+            ImOp::Mul(..) => OpTy::Mul,
+            ImOp::Div(..) => OpTy::Div,
             ImOp::Square(..) => OpTy::Square,
+            ImOp::Neg(..) => OpTy::Neg,
+            ImOp::Exp(..) => OpTy::Exp,
+            ImOp::Log(..) => OpTy::Log,
+            ImOp::Sqrt(..) => OpTy::Sqrt,
+            ImOp::Tanh(..) => OpTy::Tanh,
+            ImOp::Sigmoid(..) => OpTy::Sigmoid,
+            ImOp::Relu(..) => OpTy::Relu,
+            ImOp::ReluThreshold(..) => OpTy::ReluThreshold,
+            ImOp::Pow(..) => OpTy::Pow,
+            ImOp::Max(..) => OpTy::Max,
+            ImOp::Min(..) => OpTy::Min,
+            ImOp::Sum(..) => OpTy::Sum,
+            ImOp::Mean(..) => OpTy::Mean,
+            ImOp::SumAxis(..) => OpTy::SumAxis,
+            ImOp::Reshape(..) => OpTy::Reshape,
+            ImOp::Transpose(..) => OpTy::Transpose,
+            ImOp::MatMul(..) => OpTy::MatMul,
+            ImOp::Const(..) => OpTy::Const,
+            ImOp::SplitAt(..) => OpTy::SplitAt,
+            ImOp::Clamp(..) => OpTy::Clamp,
+            ImOp::Abs(..) => OpTy::Abs,
+            ImOp::Softmax(..) => OpTy::Softmax,
+            ImOp::LogSoftmax(..) => OpTy::LogSoftmax,
+            ImOp::Broadcast(..) => OpTy::Broadcast,
+            ImOp::Concat(..) => OpTy::Concat,
+            ImOp::Stack(..) => OpTy::Stack,
+            ImOp::Slice(..) => OpTy::Slice,
+            ImOp::Dropout(..) => OpTy::Dropout,
+            ImOp::Reciprocal(..) => OpTy::Reciprocal,
+            ImOp::Sin(..) => OpTy::Sin,
+            ImOp::Cos(..) => OpTy::Cos,
+            ImOp::Pad(..) => OpTy::Pad,
+            ImOp::Conv2d(..) => OpTy::Conv2d,
+            ImOp::MaxPool2d(..) => OpTy::MaxPool2d,
+            ImOp::Embedding(..) => OpTy::Embedding,
+            ImOp::BatchNorm(..) => OpTy::BatchNorm,
+            ImOp::LayerNorm(..) => OpTy::LayerNorm,
+            ImOp::Gelu(..) => OpTy::Gelu,
+            ImOp::LeakyRelu(..) => OpTy::LeakyRelu,
+            ImOp::SquaredDiff(..) => OpTy::SquaredDiff,
             ImOp::TrainConst => OpTy::TrainConst,
+            ImOp::TrainInit => OpTy::TrainInit,
         }
     }
 }
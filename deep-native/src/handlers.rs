@@ -0,0 +1,805 @@
+//! Handlers for the standard ops, so that [Native](crate::Native) can be used out of the box.
+//!
+//! Use [all] to register every one of them at once:
+//!
+//! ```
+//! use deep_native::{handlers, Native};
+//!
+//! let backend = Native::new().handlers(handlers::all());
+//! ```
+
+use crate::util::{
+    broadcast_grad, broadcast_pair, reduce_grad, sample_normal, sample_uniform, SplitMix64,
+};
+use crate::{tsor0, Handler, Native, Tsor};
+use deep::{InitKind, Op, OpTy};
+use deep_backend_tools::ImOp;
+use ndarray::Axis;
+use rand_core::RngCore;
+
+/// Returns one of each of the handlers in this module.
+pub fn all() -> Vec<Box<dyn Handler>> {
+    vec![
+        Box::new(Add),
+        Box::new(Sub),
+        Box::new(Mul),
+        Box::new(Square),
+        Box::new(SquaredDiff),
+        Box::new(Neg),
+        Box::new(Dropout),
+        Box::new(LogSoftmax),
+        Box::new(Sum),
+        Box::new(Mean),
+        Box::new(Concat),
+        Box::new(Stack),
+        Box::new(BatchNorm::default()),
+        Box::new(Const),
+        Box::new(TrainConst),
+        Box::new(TrainInit),
+    ]
+}
+
+pub struct Add;
+
+impl Handler for Add {
+    fn op(&self) -> OpTy {
+        OpTy::Add
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to an add operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Add(a, b) = imop {
+            let (a, b) = broadcast_pair(a, b);
+            vec![a + b]
+        } else {
+            panic!("got {:?} when OpTy::Add was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Add(a, b) = imop {
+            (
+                ImOp::Add(
+                    reduce_grad(output_delta.clone(), a.shape()),
+                    reduce_grad(output_delta, b.shape()),
+                ),
+                vec![],
+            )
+        } else {
+            panic!("got {:?} when OpTy::Add was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Sub;
+
+impl Handler for Sub {
+    fn op(&self) -> OpTy {
+        OpTy::Sub
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a sub operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Sub(a, b) = imop {
+            let (a, b) = broadcast_pair(a, b);
+            vec![a - b]
+        } else {
+            panic!("got {:?} when OpTy::Sub was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Sub(a, b) = imop {
+            (
+                ImOp::Sub(
+                    reduce_grad(output_delta.clone(), a.shape()),
+                    reduce_grad(-output_delta, b.shape()),
+                ),
+                vec![],
+            )
+        } else {
+            panic!("got {:?} when OpTy::Sub was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Mul;
+
+impl Handler for Mul {
+    fn op(&self) -> OpTy {
+        OpTy::Mul
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a mul operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Mul(a, b) = imop {
+            let (a, b) = broadcast_pair(a, b);
+            vec![a * b]
+        } else {
+            panic!("got {:?} when OpTy::Mul was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Mul(a, b) = imop {
+            let (a_shape, b_shape) = (a.shape().to_vec(), b.shape().to_vec());
+            let (a, b) = broadcast_pair(a, b);
+            (
+                ImOp::Mul(
+                    reduce_grad(b * output_delta.clone(), &a_shape),
+                    reduce_grad(a * output_delta, &b_shape),
+                ),
+                vec![],
+            )
+        } else {
+            panic!("got {:?} when OpTy::Mul was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Square;
+
+impl Handler for Square {
+    fn op(&self) -> OpTy {
+        OpTy::Square
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a square operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Square(a) = imop {
+            vec![a.mapv(|n| n.powi(2)).to_shared()]
+        } else {
+            panic!("got {:?} when OpTy::Square was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Square(a) = imop {
+            (ImOp::Square(2.0 * a * output_delta), vec![])
+        } else {
+            panic!("got {:?} when OpTy::Square was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct SquaredDiff;
+
+impl Handler for SquaredDiff {
+    fn op(&self) -> OpTy {
+        OpTy::SquaredDiff
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a squared difference operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::SquaredDiff(a, b) = imop {
+            let (a, b) = broadcast_pair(a, b);
+            vec![(a - b).mapv(|n| n.powi(2)).to_shared()]
+        } else {
+            panic!(
+                "got {:?} when OpTy::SquaredDiff was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::SquaredDiff(a, b) = imop {
+            let (a_shape, b_shape) = (a.shape().to_vec(), b.shape().to_vec());
+            let (a, b) = broadcast_pair(a, b);
+            let delta = 2.0 * (a - b) * output_delta;
+            (
+                ImOp::SquaredDiff(
+                    reduce_grad(delta.clone(), &a_shape),
+                    reduce_grad(-delta, &b_shape),
+                ),
+                vec![],
+            )
+        } else {
+            panic!(
+                "got {:?} when OpTy::SquaredDiff was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+}
+
+pub struct Neg;
+
+impl Handler for Neg {
+    fn op(&self) -> OpTy {
+        OpTy::Neg
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a neg operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Neg(a) = imop {
+            vec![-a]
+        } else {
+            panic!("got {:?} when OpTy::Neg was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        let ty: OpTy = (&imop).into();
+        assert_eq!(ty, OpTy::Neg);
+        (ImOp::Neg(-output_delta), vec![])
+    }
+}
+
+pub struct Dropout;
+
+impl Dropout {
+    /// The scale of every element: `0` for the dropped ones and `1 / (1 - p)` for the kept ones.
+    fn mask(shape: &[usize], p: f64, seed: u64) -> Tsor {
+        let mut rng = SplitMix64(seed);
+        let keep = (1.0 / (1.0 - p)) as f32;
+        Tsor::from_shape_fn(shape, |_| {
+            if sample_uniform(&mut rng, 0.0, 1.0) < p {
+                0.0
+            } else {
+                keep
+            }
+        })
+    }
+}
+
+impl Handler for Dropout {
+    fn op(&self) -> OpTy {
+        OpTy::Dropout
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        match imop {
+            ImOp::Dropout(a, p, Some(seed)) => {
+                vec![(&a * &Self::mask(a.shape(), p, seed)).into_shared()]
+            }
+            ImOp::Dropout(a, _, None) => vec![a],
+            _ => panic!(
+                "got {:?} when OpTy::Dropout was expected",
+                OpTy::from(&imop)
+            ),
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        match imop {
+            ImOp::Dropout(_, p, Some(seed)) => {
+                let mask = Self::mask(output_delta.shape(), p, seed);
+                (
+                    ImOp::Dropout((&output_delta * &mask).into_shared(), p, Some(seed)),
+                    vec![],
+                )
+            }
+            ImOp::Dropout(_, p, None) => (ImOp::Dropout(output_delta, p, None), vec![]),
+            _ => panic!(
+                "got {:?} when OpTy::Dropout was expected",
+                OpTy::from(&imop)
+            ),
+        }
+    }
+}
+
+pub struct LogSoftmax;
+
+impl LogSoftmax {
+    /// Subtracts the maximum along the axis before exponentiating, so nothing overflows.
+    fn log_softmax(a: &Tsor, axis: usize) -> Tsor {
+        let axis = Axis(axis);
+        let max = a
+            .fold_axis(axis, f32::NEG_INFINITY, |&max, &n| max.max(n))
+            .insert_axis(axis);
+        let shifted = a - &max;
+        let log_sum = shifted
+            .mapv(f32::exp)
+            .sum_axis(axis)
+            .mapv(f32::ln)
+            .insert_axis(axis);
+        (shifted - &log_sum).into_shared()
+    }
+}
+
+impl Handler for LogSoftmax {
+    fn op(&self) -> OpTy {
+        OpTy::LogSoftmax
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a log softmax operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::LogSoftmax(a, axis) = imop {
+            vec![Self::log_softmax(&a, axis)]
+        } else {
+            panic!(
+                "got {:?} when OpTy::LogSoftmax was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::LogSoftmax(a, axis) = imop {
+            // d/dx_i = delta_i - softmax_i * sum(delta) along the axis.
+            let softmax = Self::log_softmax(&a, axis).mapv(f32::exp);
+            let delta_sum = output_delta.sum_axis(Axis(axis)).insert_axis(Axis(axis));
+            let grad = &output_delta - &(softmax * &delta_sum);
+            (ImOp::LogSoftmax(grad.into_shared(), axis), vec![])
+        } else {
+            panic!(
+                "got {:?} when OpTy::LogSoftmax was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+}
+
+pub struct Sum;
+
+impl Handler for Sum {
+    fn op(&self) -> OpTy {
+        OpTy::Sum
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a sum operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Sum(a) = imop {
+            vec![tsor0(a.sum())]
+        } else {
+            panic!("got {:?} when OpTy::Sum was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Sum(a) = imop {
+            (
+                ImOp::Sum(broadcast_grad(a.shape(), output_delta.sum())),
+                vec![],
+            )
+        } else {
+            panic!("got {:?} when OpTy::Sum was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Mean;
+
+impl Handler for Mean {
+    fn op(&self) -> OpTy {
+        OpTy::Mean
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a mean operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Mean(a) = imop {
+            vec![tsor0(a.sum() / a.len() as f32)]
+        } else {
+            panic!("got {:?} when OpTy::Mean was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Mean(a) = imop {
+            let grad = output_delta.sum() / a.len() as f32;
+            (ImOp::Mean(broadcast_grad(a.shape(), grad)), vec![])
+        } else {
+            panic!("got {:?} when OpTy::Mean was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Concat;
+
+impl Handler for Concat {
+    fn op(&self) -> OpTy {
+        OpTy::Concat
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a concat operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Concat(tensors, axis) = imop {
+            let views: Vec<_> = tensors.iter().map(|tensor| tensor.view()).collect();
+            vec![ndarray::stack(Axis(axis), &views)
+                .expect("unable to concatenate tensors of incompatible shapes")
+                .into_shared()]
+        } else {
+            panic!("got {:?} when OpTy::Concat was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Concat(tensors, axis) = imop {
+            // Every input gets back the part of the delta where it was placed.
+            let mut start = 0;
+            let deltas = tensors
+                .iter()
+                .map(|tensor| {
+                    let end = start + tensor.len_of(Axis(axis));
+                    let delta = output_delta.slice_axis(Axis(axis), (start..end).into());
+                    start = end;
+                    delta.to_shared()
+                })
+                .collect();
+            (ImOp::Concat(deltas, axis), vec![])
+        } else {
+            panic!("got {:?} when OpTy::Concat was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+pub struct Stack;
+
+impl Handler for Stack {
+    fn op(&self) -> OpTy {
+        OpTy::Stack
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // There are no internal variables to a stack operation.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Stack(tensors, axis) = imop {
+            let views: Vec<_> = tensors
+                .iter()
+                .map(|tensor| tensor.view().insert_axis(Axis(axis)))
+                .collect();
+            vec![ndarray::stack(Axis(axis), &views)
+                .expect("unable to stack tensors of different shapes")
+                .into_shared()]
+        } else {
+            panic!("got {:?} when OpTy::Stack was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::Stack(tensors, axis) = imop {
+            // Every input gets back the slice of the delta at its index along the new axis.
+            let deltas = (0..tensors.len())
+                .map(|index| output_delta.index_axis(Axis(axis), index).to_shared())
+                .collect();
+            (ImOp::Stack(deltas, axis), vec![])
+        } else {
+            panic!("got {:?} when OpTy::Stack was expected", OpTy::from(&imop));
+        }
+    }
+}
+
+/// Keeps the running mean and variance of the batches as its state, starting from `0` and `1`.
+pub struct BatchNorm {
+    /// How much of the statistics of every new batch go into the running statistics.
+    pub momentum: f32,
+    /// Added to the variance before taking its square root, to avoid dividing by zero.
+    pub epsilon: f32,
+}
+
+impl Default for BatchNorm {
+    fn default() -> Self {
+        Self {
+            momentum: 0.1,
+            epsilon: 1e-5,
+        }
+    }
+}
+
+impl BatchNorm {
+    /// The mean and variance of every feature over the batch, the first axis.
+    fn statistics(input: &Tsor) -> (Tsor, Tsor) {
+        let mean = input.mean_axis(Axis(0)).unwrap();
+        let variance = (input - &mean).mapv(|n| n * n).mean_axis(Axis(0)).unwrap();
+        (mean.into_shared(), variance.into_shared())
+    }
+
+    /// The statistics to normalize with: the batch ones while training, otherwise the running
+    /// ones from the state.
+    fn used_statistics(input: &Tsor, training: bool, state: &[Tsor]) -> (Tsor, Tsor) {
+        if training {
+            Self::statistics(input)
+        } else {
+            (state[0].clone(), state[1].clone())
+        }
+    }
+}
+
+impl Handler for BatchNorm {
+    fn op(&self) -> OpTy {
+        OpTy::BatchNorm
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // The number of features isn't known yet, so the statistics broadcast until they are.
+        vec![tsor0(0.0), tsor0(1.0)]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::BatchNorm(input, gamma, beta, training) = imop {
+            let (mean, variance) = Self::used_statistics(&input, training, state);
+            let epsilon = self.epsilon;
+            let normalized = (&input - &mean) / &variance.mapv(|n| (n + epsilon).sqrt());
+            vec![(normalized * &gamma + &beta).into_shared(), mean, variance]
+        } else {
+            panic!(
+                "got {:?} when OpTy::BatchNorm was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+
+    fn update_state(&self, outputs: &[Tsor], state: &mut Vec<Tsor>) {
+        let momentum = self.momentum;
+        for (running, batch) in state.iter_mut().zip(&outputs[1..]) {
+            *running = (batch.mapv(|n| momentum * n) + &running.mapv(|n| (1.0 - momentum) * n))
+                .into_shared();
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        state: &[Tsor],
+        (output, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        if let ImOp::BatchNorm(input, gamma, beta, training) = imop {
+            if output != 0 {
+                // The statistics are not trained through.
+                let zeros = |t: &Tsor| Tsor::zeros(t.shape());
+                let deltas = ImOp::BatchNorm(zeros(&input), zeros(&gamma), zeros(&beta), training);
+                return (deltas, vec![]);
+            }
+            let (mean, variance) = Self::used_statistics(&input, training, state);
+            let epsilon = self.epsilon;
+            let inverse_std = variance.mapv(|n| 1.0 / (n + epsilon).sqrt());
+            let normalized = (&input - &mean) * &inverse_std;
+
+            let gamma_delta = (&output_delta * &normalized).sum_axis(Axis(0));
+            let beta_delta = output_delta.sum_axis(Axis(0));
+            let normalized_delta = &output_delta * &gamma;
+            let input_delta = if training {
+                // The batch statistics depend on every element of the batch as well.
+                let count = input.len_of(Axis(0)) as f32;
+                let sum = normalized_delta.sum_axis(Axis(0));
+                let dot = (&normalized_delta * &normalized).sum_axis(Axis(0));
+                (normalized_delta.mapv(|n| n * count) - &sum - normalized * &dot) * &inverse_std
+                    / count
+            } else {
+                normalized_delta * &inverse_std
+            };
+            (
+                ImOp::BatchNorm(
+                    input_delta.into_shared(),
+                    gamma_delta.into_shared(),
+                    beta_delta.into_shared(),
+                    training,
+                ),
+                vec![],
+            )
+        } else {
+            panic!(
+                "got {:?} when OpTy::BatchNorm was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+}
+
+pub struct Const;
+
+impl Handler for Const {
+    fn op(&self) -> OpTy {
+        OpTy::Const
+    }
+
+    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        // The value is part of the op, so there is nothing to store.
+        vec![]
+    }
+
+    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::Const(shape, value) = imop {
+            vec![Tsor::from_elem(&shape[..], value as f32)]
+        } else {
+            panic!("got {:?} when OpTy::Const was expected", OpTy::from(&imop));
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        _output_delta: (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        let ty: OpTy = (&imop).into();
+        assert_eq!(ty, OpTy::Const);
+        // Constants are never trained.
+        (imop, vec![])
+    }
+}
+
+pub struct TrainConst;
+
+impl Handler for TrainConst {
+    fn op(&self) -> OpTy {
+        OpTy::TrainConst
+    }
+
+    fn generate_state(&self, op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
+        if let Op::TrainConst(shape, value) = op {
+            vec![Tsor::zeros(&shape[..]) + *value as f32]
+        } else {
+            panic!("got {:?} when Op::TrainConst was expected", OpTy::from(op));
+        }
+    }
+
+    fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::TrainConst = imop {
+            vec![state[0].clone()]
+        } else {
+            panic!(
+                "got {:?} when OpTy::TrainConst was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        let ty: OpTy = (&imop).into();
+        assert_eq!(ty, OpTy::TrainConst);
+        (ImOp::TrainConst, vec![output_delta])
+    }
+}
+
+pub struct TrainInit;
+
+impl Handler for TrainInit {
+    fn op(&self) -> OpTy {
+        OpTy::TrainInit
+    }
+
+    fn generate_state(&self, op: &Op, rng: &mut dyn RngCore) -> Vec<Tsor> {
+        if let Op::TrainInit(shape, kind) = op {
+            let mut sample: Box<dyn FnMut() -> f64> = match *kind {
+                InitKind::Const(value) => Box::new(move || value),
+                InitKind::Uniform { low, high } => Box::new(move || sample_uniform(rng, low, high)),
+                InitKind::Normal { mean, std } => Box::new(move || sample_normal(rng, mean, std)),
+                InitKind::Xavier => {
+                    let fan_in = shape.first().copied().unwrap_or(1);
+                    let fan_out = shape.iter().skip(1).product::<usize>();
+                    let limit = (6.0 / (fan_in + fan_out) as f64).sqrt();
+                    Box::new(move || sample_uniform(rng, -limit, limit))
+                }
+            };
+            vec![Tsor::from_shape_fn(&shape[..], |_| sample() as f32)]
+        } else {
+            panic!("got {:?} when Op::TrainInit was expected", OpTy::from(op));
+        }
+    }
+
+    fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor> {
+        if let ImOp::TrainInit = imop {
+            vec![state[0].clone()]
+        } else {
+            panic!(
+                "got {:?} when OpTy::TrainInit was expected",
+                OpTy::from(&imop)
+            );
+        }
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        _state: &[Tsor],
+        (_, output_delta): (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        let ty: OpTy = (&imop).into();
+        assert_eq!(ty, OpTy::TrainInit);
+        (ImOp::TrainInit, vec![output_delta])
+    }
+}
@@ -1,9 +1,17 @@
+pub mod handlers;
+pub mod optimizers;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod util;
+
 use deep::*;
 use deep_backend_tools::*;
 use ndarray::{ArcArray, IxDyn};
 use rand_core::RngCore;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::iter::{Extend, FromIterator};
+use util::SplitMix64;
 
 pub type Tsor = ArcArray<f32, IxDyn>;
 
@@ -35,11 +43,20 @@ pub trait Handler {
     fn op(&self) -> OpTy;
 
     /// This generates the trainable state for this graph node.
+    ///
+    /// The state must only depend on the op and the values drawn from `rng`, see
+    /// [Backend::state].
     fn generate_state(&self, op: &Op, rng: &mut dyn RngCore) -> Vec<Tsor>;
 
     /// This performs forward propogation for the op.
     fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor>;
 
+    /// This updates the state of the op after a training forward pass produced `outputs`, see
+    /// [Backend::update_state].
+    ///
+    /// Most ops have no state besides their trainable one, so the default does nothing.
+    fn update_state(&self, _outputs: &[Tsor], _state: &mut Vec<Tsor>) {}
+
     /// This performs backward propogation for the op.
     ///
     /// Returns an `ImOp` of the input gradients along with the trainable state deltas (if any).
@@ -54,6 +71,8 @@ pub trait Handler {
 #[derive(Default)]
 pub struct Native {
     handlers: HashMap<OpTy, Box<dyn Handler>>,
+    /// Seeds every [Backend::forward_train].
+    rng: RefCell<SplitMix64>,
 }
 
 impl Native {
@@ -71,6 +90,12 @@ impl Native {
         self
     }
 
+    /// Use this to seed the rng used by [Backend::forward_train], which starts from `0` otherwise.
+    pub fn seed(self, seed: u64) -> Self {
+        self.rng.replace(SplitMix64(seed));
+        self
+    }
+
     /// Use this to add several handlers at once, such as from a library.
     pub fn handlers<I>(mut self, iter: I) -> Self
     where
@@ -117,18 +142,21 @@ impl Backend for Native {
     type Error = Error;
 
     /// Generates the initial state for a graph.
+    ///
+    /// The handlers generate the state of the ops in the order of the graph, so the same seed
+    /// always produces the same state as long as each handler is deterministic in the rng.
     fn state<R>(&self, graph: &Graph, mut rng: R) -> Result<Self::State>
     where
         R: RngCore,
     {
         graph
-            .ops
+            .ops()
             .iter()
             .map(|op| {
                 let ty = op.into();
                 self.handlers
                     .get(&ty)
-                    .ok_or_else(|| Error::OpHasNoHandler { ty })
+                    .ok_or(Error::OpHasNoHandler { ty })
                     .map(|handler| handler.generate_state(op, &mut rng))
             })
             .collect()
@@ -147,6 +175,84 @@ impl Backend for Native {
             .map(|tensor| (tensor, tape))
     }
 
+    /// Gets the outputs of solving several requested tensors, sharing one tape between them so
+    /// that their common ops are only computed once.
+    fn forward_many(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        outputs: &[Input],
+    ) -> Result<Vec<Self::Tensor>> {
+        let mut tape = Tape::new();
+        outputs
+            .iter()
+            .map(|output| tape.solve(self, graph, &state[..], inputs, output.clone()))
+            .collect()
+    }
+
+    /// Uses the rng of the backend with [Backend::forward_stochastic].
+    fn forward_train(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+    ) -> Result<(Self::Tensor, Self::InternalStorage)> {
+        let mut rng = self.rng.borrow_mut();
+        self.forward_stochastic(graph, state, inputs, tensor, &mut *rng)
+    }
+
+    /// Draws one seed from `rng` per call, which the stochastic ops derive their randomness from.
+    fn forward_stochastic<R>(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+        rng: &mut R,
+    ) -> Result<(Self::Tensor, Self::InternalStorage)>
+    where
+        R: RngCore,
+    {
+        let mut tape = Tape::training(rng.next_u64());
+        tape.solve(self, graph, &state[..], inputs, tensor)
+            .map(|tensor| (tensor, tape))
+    }
+
+    /// Reuses the cached tape as long as the graph has the same fingerprint and every feed the
+    /// tape used is equal to the one in `inputs`, so only ops which weren't solved yet are
+    /// computed. Feeds sharing the buffer and strides of the cached ones are known to be equal
+    /// without comparing their elements, since the tape holds on to them.
+    fn forward_cached(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+        cache: &mut Option<(u64, Self::InternalStorage)>,
+    ) -> Result<Self::Tensor> {
+        let fingerprint = graph.fingerprint();
+        let unchanged = |(cached, tape): &(u64, Tape<Self>)| {
+            *cached == fingerprint
+                && tape.fed().iter().all(|(name, fed)| {
+                    inputs.get(name).is_some_and(|input| {
+                        input.shape() == fed.shape()
+                            && ((input.as_ptr() == fed.as_ptr()
+                                && input.strides() == fed.strides())
+                                || input == fed)
+                    })
+                })
+        };
+        if !cache.as_ref().is_some_and(unchanged) {
+            *cache = None;
+        }
+        cache
+            .get_or_insert_with(|| (fingerprint, Tape::new()))
+            .1
+            .solve(self, graph, &state[..], inputs, tensor)
+    }
+
     /// Propogates a delta from the output back to the input via chain rule
     /// and produces a `Delta` that can be used to update the graph
     /// with an optimizer. The `Delta` contains all the dE/dx of all internal
@@ -171,6 +277,54 @@ impl Backend for Native {
         )
     }
 
+    /// Backpropagates every output into the same accumulator, so their gradients are summed as
+    /// they are produced.
+    fn backward_many(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        internal: &Self::InternalStorage,
+        inputs: &Self::TensorDict,
+        outputs: &[(Input, Self::Tensor)],
+    ) -> Result<Self::Delta> {
+        outputs.iter().try_fold(
+            AccumulateTensors::new(),
+            |deltas, (output, output_delta)| {
+                internal.backprop(
+                    self,
+                    graph,
+                    &state[..],
+                    inputs,
+                    output.clone(),
+                    output_delta.clone(),
+                    deltas,
+                )
+            },
+        )
+    }
+
+    /// Lets the handler of every op solved by a training tape update its state.
+    fn update_state(
+        &self,
+        graph: &Graph,
+        state: &mut Self::State,
+        internal: &Self::InternalStorage,
+    ) -> Result<()> {
+        if !internal.is_training() {
+            return Ok(());
+        }
+        for (node, op) in graph.ops().iter().enumerate() {
+            if let Some(outputs) = internal.solved().get(&Internal { node, output: 0 }) {
+                let ty = op.into();
+                self.handlers
+                    .get(&ty)
+                    .ok_or(Error::OpHasNoHandler { ty })?
+                    .update_state(outputs, &mut state[node]);
+            }
+        }
+        Ok(())
+    }
+
     /// Applies a delta to the graph.
     fn train(&self, state: &mut Self::State, delta: &Self::Delta) -> Result<()> {
         for (&node, deltas) in &delta.table {
@@ -180,6 +334,94 @@ impl Backend for Native {
         }
         Ok(())
     }
+
+    /// Applies a delta to the graph, skipping its frozen nodes.
+    fn train_unfrozen(
+        &self,
+        graph: &Graph,
+        state: &mut Self::State,
+        delta: &Self::Delta,
+    ) -> Result<()> {
+        for (&node, deltas) in &delta.table {
+            if graph.is_frozen(node) {
+                continue;
+            }
+            for (a, b) in state[node].iter_mut().zip(deltas.iter()) {
+                *a += b;
+            }
+        }
+        Ok(())
+    }
+
+    fn train_logged(
+        &self,
+        graph: &Graph,
+        state: &mut Self::State,
+        delta: &Self::Delta,
+    ) -> Result<HashMap<usize, f32>> {
+        let norms = delta
+            .table
+            .iter()
+            // Nodes without state have empty deltas and aren't trained, nor are frozen ones.
+            .filter(|(&node, deltas)| !deltas.is_empty() && !graph.is_frozen(node))
+            .map(|(&node, deltas)| {
+                let squares: f32 = deltas.iter().flatten().map(|n| n * n).sum();
+                (node, squares.sqrt())
+            })
+            .collect();
+        self.train_unfrozen(graph, state, delta)?;
+        Ok(norms)
+    }
+
+    /// Serializes the state with bincode, which needs the `serde` feature.
+    #[cfg(feature = "serde")]
+    fn save_state(&self, state: &Self::State) -> Result<Vec<u8>> {
+        bincode::serialize(state).map_err(|error| Error::State {
+            reason: error.to_string(),
+        })
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&self, bytes: &[u8]) -> Result<Self::State> {
+        bincode::deserialize(bytes).map_err(|error| Error::State {
+            reason: error.to_string(),
+        })
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn save_state(&self, _state: &Self::State) -> Result<Vec<u8>> {
+        Err(without_serde())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn load_state(&self, _bytes: &[u8]) -> Result<Self::State> {
+        Err(without_serde())
+    }
+}
+
+impl StateDict for Native {
+    /// Trainable ops keep their parameter as their only state tensor.
+    fn state_dict<'a>(&self, graph: &Graph, state: &'a Self::State) -> HashMap<usize, &'a Tsor> {
+        state
+            .iter()
+            .enumerate()
+            .filter(|&(node, _)| is_trainable(graph, node))
+            .filter_map(|(node, tensors)| tensors.first().map(|tensor| (node, tensor)))
+            .collect()
+    }
+
+    fn state_dict_mut<'a>(
+        &self,
+        graph: &Graph,
+        state: &'a mut Self::State,
+    ) -> HashMap<usize, &'a mut Tsor> {
+        state
+            .iter_mut()
+            .enumerate()
+            .filter(|&(node, _)| is_trainable(graph, node))
+            .filter_map(|(node, tensors)| tensors.first_mut().map(|tensor| (node, tensor)))
+            .collect()
+    }
 }
 
 impl Immediate for Native {
@@ -204,3 +446,19 @@ impl Propogate for Native {
             .map(|handler| handler.backward(imop, state, output_delta))
     }
 }
+
+/// Whether the op at `node` is a trainable parameter, whose state is its value.
+fn is_trainable(graph: &Graph, node: usize) -> bool {
+    matches!(
+        graph.ops().get(node),
+        Some(Op::TrainConst(..) | Op::TrainInit(..))
+    )
+}
+
+/// The error for saving and loading the state without the `serde` feature.
+#[cfg(not(feature = "serde"))]
+fn without_serde() -> Error {
+    Error::State {
+        reason: "deep-native was built without the \"serde\" feature".to_owned(),
+    }
+}
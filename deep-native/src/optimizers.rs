@@ -0,0 +1,353 @@
+//! [Optimizer]s for the [Native] backend.
+
+use crate::{Native, Tsor};
+use deep::{Backend, Graph, Optimizer};
+use deep_backend_tools::{AccumulateTensors, Result};
+use std::collections::HashMap;
+
+/// A delta whose gradients can be rescaled to limit their size.
+pub trait ClippableDelta {
+    /// The L2 norm of all the gradients in the delta taken together.
+    fn global_norm(&self) -> f32;
+
+    /// Scales all the gradients so that their [global norm](ClippableDelta::global_norm)
+    /// is at most `max_norm`. Deltas which are already small enough are left untouched.
+    fn clip_grad_norm(&mut self, max_norm: f32);
+}
+
+impl ClippableDelta for AccumulateTensors<Tsor> {
+    fn global_norm(&self) -> f32 {
+        self.table
+            .values()
+            .flatten()
+            .map(|d| d.iter().map(|n| n * n).sum::<f32>())
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    fn clip_grad_norm(&mut self, max_norm: f32) {
+        let norm = self.global_norm();
+        if norm > max_norm {
+            let scale = max_norm / norm;
+            for d in self.table.values_mut().flatten() {
+                d.mapv_inplace(|n| n * scale);
+            }
+        }
+    }
+}
+
+/// Wraps another optimizer, clipping the global norm of every delta before passing it on.
+pub struct ClipGradNorm<O> {
+    pub max_norm: f32,
+    pub optimizer: O,
+}
+
+impl<O> ClipGradNorm<O> {
+    pub fn new(max_norm: f32, optimizer: O) -> Self {
+        Self {
+            max_norm,
+            optimizer,
+        }
+    }
+
+    fn clip(&self, delta: &AccumulateTensors<Tsor>) -> AccumulateTensors<Tsor> {
+        let mut clipped = AccumulateTensors {
+            table: delta.table.clone(),
+        };
+        clipped.clip_grad_norm(self.max_norm);
+        clipped
+    }
+}
+
+impl<O> Optimizer<Native> for ClipGradNorm<O>
+where
+    O: Optimizer<Native>,
+{
+    fn apply(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<()> {
+        let clipped = self.clip(delta);
+        self.optimizer.apply(backend, graph, state, &clipped)
+    }
+
+    fn apply_logged(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<HashMap<usize, f32>> {
+        let clipped = self.clip(delta);
+        self.optimizer.apply_logged(backend, graph, state, &clipped)
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.optimizer.set_learning_rate(learning_rate);
+    }
+}
+
+/// Plain stochastic gradient descent, which steps against the delta scaled by the learning rate.
+pub struct Sgd {
+    pub learning_rate: f32,
+    /// Learning rates which override `learning_rate` for the state of some graph nodes, such as
+    /// to fine-tune a new head faster than a pretrained backbone.
+    pub node_rates: HashMap<usize, f32>,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            node_rates: HashMap::new(),
+        }
+    }
+
+    /// Puts the trainable `nodes` in a group which uses `learning_rate` instead of the one of the
+    /// optimizer.
+    ///
+    /// Changing the learning rate of the optimizer, like a [Schedule](deep::Schedule) does,
+    /// doesn't affect the groups.
+    pub fn group(mut self, nodes: impl IntoIterator<Item = usize>, learning_rate: f32) -> Self {
+        self.node_rates
+            .extend(nodes.into_iter().map(|node| (node, learning_rate)));
+        self
+    }
+
+    /// The step against `delta`, scaled by the learning rate of each node.
+    fn step(&self, delta: &AccumulateTensors<Tsor>) -> AccumulateTensors<Tsor> {
+        AccumulateTensors {
+            table: delta
+                .table
+                .iter()
+                .map(|(&node, deltas)| {
+                    let learning_rate = self
+                        .node_rates
+                        .get(&node)
+                        .copied()
+                        .unwrap_or(self.learning_rate);
+                    let deltas = deltas
+                        .iter()
+                        .map(|d| d.mapv(|n| -learning_rate * n).into_shared())
+                        .collect();
+                    (node, deltas)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Optimizer<Native> for Sgd {
+    fn apply(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<()> {
+        let step = self.step(delta);
+        backend.train_unfrozen(graph, state, &step)
+    }
+
+    fn apply_logged(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<HashMap<usize, f32>> {
+        let step = self.step(delta);
+        backend.train_logged(graph, state, &step)
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+/// The Adam optimizer, which scales the step of every parameter using running estimates of the
+/// first and second moments of its deltas.
+///
+/// The moment estimates are kept per graph node and persist between calls to `apply`.
+pub struct Adam {
+    pub learning_rate: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    /// The estimate of the mean of the deltas of each node.
+    pub first_moments: HashMap<usize, Vec<Tsor>>,
+    /// The estimate of the uncentered variance of the deltas of each node.
+    pub second_moments: HashMap<usize, Vec<Tsor>>,
+    /// The number of steps applied so far, used to correct the bias of the estimates.
+    pub steps: i32,
+}
+
+impl Adam {
+    /// Creates an Adam optimizer with the commonly used defaults for everything except
+    /// the learning rate.
+    pub fn new(learning_rate: f32) -> Self {
+        Self {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+            first_moments: HashMap::new(),
+            second_moments: HashMap::new(),
+            steps: 0,
+        }
+    }
+
+    /// The step against `delta`, updating the moment estimates.
+    fn step(&mut self, delta: &AccumulateTensors<Tsor>) -> AccumulateTensors<Tsor> {
+        self.steps += 1;
+        let Self {
+            learning_rate,
+            beta1,
+            beta2,
+            epsilon,
+            steps,
+            ..
+        } = *self;
+        let first_correction = 1.0 - beta1.powi(steps);
+        let second_correction = 1.0 - beta2.powi(steps);
+
+        let mut step = AccumulateTensors::new();
+        for (&node, deltas) in &delta.table {
+            let zeros = || deltas.iter().map(|d| Tsor::zeros(d.shape())).collect();
+            let first = self.first_moments.entry(node).or_insert_with(zeros);
+            let second = self.second_moments.entry(node).or_insert_with(zeros);
+            let node_step = deltas
+                .iter()
+                .zip(first.iter_mut())
+                .zip(second.iter_mut())
+                .map(|((d, m), v)| {
+                    *m = (m.mapv(|n| beta1 * n) + d.mapv(|n| (1.0 - beta1) * n)).into_shared();
+                    *v = (v.mapv(|n| beta2 * n) + d.mapv(|n| (1.0 - beta2) * n * n)).into_shared();
+                    let mut s = m.mapv(|n| n / first_correction);
+                    s.zip_mut_with(v, |s, &v| {
+                        *s = -learning_rate * *s / ((v / second_correction).sqrt() + epsilon)
+                    });
+                    s.into_shared()
+                })
+                .collect();
+            step.table.insert(node, node_step);
+        }
+        step
+    }
+}
+
+impl Optimizer<Native> for Adam {
+    fn apply(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<()> {
+        let step = self.step(delta);
+        backend.train_unfrozen(graph, state, &step)
+    }
+
+    fn apply_logged(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<HashMap<usize, f32>> {
+        let step = self.step(delta);
+        backend.train_logged(graph, state, &step)
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
+
+/// Stochastic gradient descent with momentum and weight decay.
+///
+/// Every step computes `velocity = momentum * velocity + delta + weight_decay * parameter` and
+/// moves the parameter by `-learning_rate * velocity`. The velocities are kept per graph node and
+/// persist between calls to `apply`.
+pub struct SgdMomentum {
+    pub learning_rate: f32,
+    pub momentum: f32,
+    pub weight_decay: f32,
+    pub velocities: HashMap<usize, Vec<Tsor>>,
+}
+
+impl SgdMomentum {
+    pub fn new(learning_rate: f32, momentum: f32, weight_decay: f32) -> Self {
+        Self {
+            learning_rate,
+            momentum,
+            weight_decay,
+            velocities: HashMap::new(),
+        }
+    }
+
+    /// The step against `delta` and the weight decay of `state`, updating the velocities.
+    fn step(
+        &mut self,
+        state: &[Vec<Tsor>],
+        delta: &AccumulateTensors<Tsor>,
+    ) -> AccumulateTensors<Tsor> {
+        let Self {
+            learning_rate,
+            momentum,
+            weight_decay,
+            ..
+        } = *self;
+
+        let mut step = AccumulateTensors::new();
+        for (&node, deltas) in &delta.table {
+            let velocities = self
+                .velocities
+                .entry(node)
+                .or_insert_with(|| deltas.iter().map(|d| Tsor::zeros(d.shape())).collect());
+            let node_step = deltas
+                .iter()
+                .zip(&state[node])
+                .zip(velocities.iter_mut())
+                .map(|((d, p), v)| {
+                    *v =
+                        (v.mapv(|n| momentum * n) + d + p.mapv(|n| weight_decay * n)).into_shared();
+                    v.mapv(|n| -learning_rate * n).into_shared()
+                })
+                .collect();
+            step.table.insert(node, node_step);
+        }
+        step
+    }
+}
+
+impl Optimizer<Native> for SgdMomentum {
+    fn apply(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<()> {
+        let step = self.step(state, delta);
+        backend.train_unfrozen(graph, state, &step)
+    }
+
+    fn apply_logged(
+        &mut self,
+        backend: &Native,
+        graph: &Graph,
+        state: &mut Vec<Vec<Tsor>>,
+        delta: &AccumulateTensors<Tsor>,
+    ) -> Result<HashMap<usize, f32>> {
+        let step = self.step(state, delta);
+        backend.train_logged(graph, state, &step)
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+}
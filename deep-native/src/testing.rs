@@ -0,0 +1,64 @@
+//! Utilities for verifying [Handler](crate::Handler) implementations.
+
+use crate::{Native, Tsor};
+use deep::{Backend, Tensor};
+use deep_backend_tools::Result;
+use std::collections::HashMap;
+
+/// Compares the analytic gradient of `loss` computed with [Backend::backward] against a
+/// numeric one computed with central finite differences of size `epsilon`.
+///
+/// Every element of every state tensor is treated as a trainable parameter and the loss is the
+/// sum of all the elements of the output of `loss`.
+///
+/// Returns the maximum relative error over all the parameters.
+pub fn grad_check(
+    backend: &Native,
+    loss: &Tensor,
+    state: &[Vec<Tsor>],
+    inputs: &HashMap<String, Tsor>,
+    epsilon: f32,
+) -> Result<f32> {
+    let state = state.to_vec();
+    let graph = loss.graph();
+    let eval = |state: &Vec<Vec<Tsor>>| {
+        backend
+            .forward(&graph, state, inputs, loss.input().clone())
+            .map(|(output, _)| output.sum())
+    };
+
+    let (output, internal) = backend.forward(&graph, &state, inputs, loss.input().clone())?;
+    let delta = backend.backward(
+        &graph,
+        &state,
+        &internal,
+        inputs,
+        loss.input().clone(),
+        Tsor::ones(output.shape()),
+    )?;
+
+    let mut max_error = 0.0f32;
+    for (node, tensors) in state.iter().enumerate() {
+        for (i, tensor) in tensors.iter().enumerate() {
+            for j in 0..tensor.len() {
+                let nudged = |amount: f32| {
+                    let mut state = state.clone();
+                    *state[node][i].iter_mut().nth(j).unwrap() += amount;
+                    eval(&state)
+                };
+                let numeric = (nudged(epsilon)? - nudged(-epsilon)?) / (2.0 * epsilon);
+                let analytic = delta
+                    .table
+                    .get(&node)
+                    .and_then(|deltas| deltas.get(i))
+                    .map_or(0.0, |delta| *delta.iter().nth(j).unwrap());
+
+                let scale = numeric.abs().max(analytic.abs());
+                if scale > 0.0 {
+                    max_error = max_error.max((numeric - analytic).abs() / scale);
+                }
+            }
+        }
+    }
+    Ok(max_error)
+}
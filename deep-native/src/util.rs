@@ -0,0 +1,104 @@
+//! Helpers shared by the [Handler](crate::Handler)s of several ops.
+
+use crate::Tsor;
+use ndarray::Axis;
+use rand_core::RngCore;
+
+/// Creates the input delta of a reduction to a scalar: a tensor of the input's `shape` where
+/// every element receives `scalar_grad`, since every element contributed to the output.
+pub fn broadcast_grad(shape: &[usize], scalar_grad: f32) -> Tsor {
+    Tsor::from_elem(shape, scalar_grad)
+}
+
+/// Broadcasts both operands of an elementwise op to their common shape, like NumPy does, since
+/// ndarray only broadcasts the right-hand one.
+///
+/// Panics if the shapes are incompatible.
+pub fn broadcast_pair(a: Tsor, b: Tsor) -> (Tsor, Tsor) {
+    if a.shape() == b.shape() {
+        return (a, b);
+    }
+    let len = a.ndim().max(b.ndim());
+    let axis = |tensor: &Tsor, i: usize| {
+        (i + tensor.ndim())
+            .checked_sub(len)
+            .map_or(1, |i| tensor.shape()[i])
+    };
+    let shape: Vec<usize> = (0..len)
+        .map(|i| match (axis(&a, i), axis(&b, i)) {
+            (x, y) if x == y => x,
+            (1, y) => y,
+            (x, 1) => x,
+            _ => panic!(
+                "unable to broadcast tensors of shapes {:?} and {:?}",
+                a.shape(),
+                b.shape()
+            ),
+        })
+        .collect();
+    let stretch = |tensor: &Tsor| tensor.broadcast(&shape[..]).unwrap().to_shared();
+    (stretch(&a), stretch(&b))
+}
+
+/// Sums the delta of a broadcast operand back down to the operand's `shape`, over the leading
+/// axes it lacked and the axes of size 1 which were stretched.
+pub fn reduce_grad(grad: Tsor, shape: &[usize]) -> Tsor {
+    if grad.shape() == shape {
+        return grad;
+    }
+    let mut grad = grad.to_owned();
+    while grad.ndim() > shape.len() {
+        grad = grad.sum_axis(Axis(0));
+    }
+    for (axis, &len) in shape.iter().enumerate() {
+        if len == 1 && grad.len_of(Axis(axis)) != 1 {
+            grad = grad.sum_axis(Axis(axis)).insert_axis(Axis(axis));
+        }
+    }
+    grad.into_shared()
+}
+
+/// A small and fast rng (SplitMix64), used where the [Native](crate::Native) backend needs
+/// randomness of its own, like the masks of [Op::Dropout](deep::Op::Dropout).
+#[derive(Clone, Debug, Default)]
+pub struct SplitMix64(pub u64);
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Samples a value uniformly from `low` (inclusive) to `high` (exclusive).
+///
+/// Only the top 53 bits of one `next_u64` are used, so the same rng state always produces the
+/// same value.
+pub fn sample_uniform(rng: &mut dyn RngCore, low: f64, high: f64) -> f64 {
+    let unit = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    low + (high - low) * unit
+}
+
+/// Samples a value from a normal distribution with the Box-Muller transform.
+pub fn sample_normal(rng: &mut dyn RngCore, mean: f64, std: f64) -> f64 {
+    // `1 - u` is in `(0, 1]`, so the logarithm is finite.
+    let radius = (-2.0 * (1.0 - sample_uniform(rng, 0.0, 1.0)).ln()).sqrt();
+    let angle = sample_uniform(rng, 0.0, 2.0 * std::f64::consts::PI);
+    mean + std * radius * angle.cos()
+}
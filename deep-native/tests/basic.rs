@@ -1,150 +1,14 @@
 use deep::*;
-use deep_backend_tools::*;
+use deep_backend_tools::ImOp;
+use deep_native::handlers::{Add, Square, Sub, TrainConst};
+use deep_native::optimizers::Sgd;
 use deep_native::*;
 use maplit::hashmap;
 use ndarray::arr1;
-use rand::{thread_rng, Rng, RngCore};
-
-struct Add;
-
-impl Handler for Add {
-    fn op(&self) -> OpTy {
-        OpTy::Add
-    }
-
-    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
-        // There are no internal variables to an add operation.
-        vec![]
-    }
-
-    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
-        if let ImOp::Add(a, b) = imop {
-            vec![a + b]
-        } else {
-            panic!("got {:?} when OpTy::Add was expected", OpTy::from(&imop));
-        }
-    }
-
-    fn backward(
-        &self,
-        imop: ImOp<Native>,
-        _state: &[Tsor],
-        (_, output_delta): (usize, Tsor),
-    ) -> (ImOp<Native>, Vec<Tsor>) {
-        let ty: OpTy = (&imop).into();
-        assert_eq!(ty, OpTy::Add);
-        (
-            ImOp::Add(output_delta.clone(), output_delta.clone()),
-            vec![],
-        )
-    }
-}
-
-struct Sub;
-
-impl Handler for Sub {
-    fn op(&self) -> OpTy {
-        OpTy::Sub
-    }
-
-    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
-        // There are no internal variables to a sub operation.
-        vec![]
-    }
-
-    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
-        if let ImOp::Sub(a, b) = imop {
-            vec![a - b]
-        } else {
-            panic!("got {:?} when OpTy::Sub was expected", OpTy::from(&imop));
-        }
-    }
-
-    fn backward(
-        &self,
-        imop: ImOp<Native>,
-        _state: &[Tsor],
-        (_, output_delta): (usize, Tsor),
-    ) -> (ImOp<Native>, Vec<Tsor>) {
-        let ty: OpTy = (&imop).into();
-        assert_eq!(ty, OpTy::Sub);
-        (ImOp::Sub(output_delta.clone(), -output_delta), vec![])
-    }
-}
-
-struct Square;
-
-impl Handler for Square {
-    fn op(&self) -> OpTy {
-        OpTy::Square
-    }
-
-    fn generate_state(&self, _op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
-        // There are no internal variables to a square operation.
-        vec![]
-    }
-
-    fn forward(&self, imop: ImOp<Native>, _state: &[Tsor]) -> Vec<Tsor> {
-        if let ImOp::Square(a) = imop {
-            vec![a.mapv(|n| n.powi(2)).to_shared()]
-        } else {
-            panic!("got {:?} when OpTy::Square was expected", OpTy::from(&imop));
-        }
-    }
-
-    fn backward(
-        &self,
-        imop: ImOp<Native>,
-        _state: &[Tsor],
-        (_, output_delta): (usize, Tsor),
-    ) -> (ImOp<Native>, Vec<Tsor>) {
-        let ty: OpTy = (&imop).into();
-        assert_eq!(ty, OpTy::Square);
-        if let ImOp::Square(a) = imop {
-            (ImOp::Square(2.0 * a * output_delta), vec![])
-        } else {
-            panic!("got {:?} when OpTy::Square was expected", OpTy::from(&imop));
-        }
-    }
-}
-
-struct TrainConst;
-
-impl Handler for TrainConst {
-    fn op(&self) -> OpTy {
-        OpTy::TrainConst
-    }
-
-    fn generate_state(&self, op: &Op, _rng: &mut dyn RngCore) -> Vec<Tsor> {
-        if let Op::TrainConst(shape, value) = op {
-            vec![Tsor::zeros(&shape[..]) + *value as f32]
-        } else {
-            panic!("got {:?} when Op::TrainConst was expected", OpTy::from(op));
-        }
-    }
-
-    fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor> {
-        if let ImOp::TrainConst = imop {
-            vec![state[0].clone()]
-        } else {
-            panic!(
-                "got {:?} when OpTy::TrainConst was expected",
-                OpTy::from(&imop)
-            );
-        }
-    }
-
-    fn backward(
-        &self,
-        imop: ImOp<Native>,
-        _state: &[Tsor],
-        (_, output_delta): (usize, Tsor),
-    ) -> (ImOp<Native>, Vec<Tsor>) {
-        let ty: OpTy = (&imop).into();
-        assert_eq!(ty, OpTy::TrainConst);
-        (ImOp::TrainConst, vec![output_delta])
-    }
-}
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use std::cell::Cell;
+use std::rc::Rc;
 
 #[test]
 fn forward_add() {
@@ -196,7 +60,7 @@ fn train_add() {
     // The learning rate.
     let learning_rate = 0.01;
 
-    let mut loss_value = std::f32::NAN;
+    let mut loss_value = f32::NAN;
 
     for _ in 0..1000 {
         // Random x value
@@ -215,7 +79,7 @@ fn train_add() {
                 &backend,
                 &mut state,
                 &feed,
-                learning_rate,
+                &mut Sgd::new(learning_rate),
                 |t| *t.iter().next().unwrap(),
                 tsor0,
             )
@@ -226,3 +90,869 @@ fn train_add() {
     // Loss starts around 25.
     assert!(loss_value < 0.1);
 }
+
+#[test]
+fn fit_train_const() {
+    let backend = Native::new().handlers(handlers::all());
+
+    // A single trainable value which should converge to the target.
+    let loss = (Tensor::train_const(vec![], 0.0) - Tensor::from("target")).squared();
+
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let feed = hashmap! {
+        "target".to_owned() => tsor0(3.0),
+    };
+
+    let losses: Vec<f32> = (0..100)
+        .map(|_| {
+            loss.gradient_descent(
+                &backend,
+                &mut state,
+                &feed,
+                &mut Sgd::new(0.01),
+                |t| *t.iter().next().unwrap(),
+                tsor0,
+            )
+            .expect("unable to train")
+        })
+        .collect();
+
+    // The loss starts at 9 and must go down at every step.
+    assert_eq!(losses[0], 9.0);
+    assert!(losses.windows(2).all(|w| w[1] < w[0]));
+    assert!(losses[99] < 1.0);
+}
+
+#[test]
+fn eval_full_internal_storage() {
+    let backend = Native::new().handlers(handlers::all());
+    let feed = hashmap! {
+        "a".to_owned() => tsor0(2.0),
+        "b".to_owned() => tsor0(3.0),
+    };
+
+    let c = (Tensor::from("a") * Tensor::from("b")).squared() + Tensor::scalar(1.0);
+    let state = c
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let (output, tape) = c
+        .eval_full(&backend, &state, &feed)
+        .expect("unable to eval");
+
+    assert_eq!(output, tsor0(37.0));
+    // Every one of the four ops was evaluated and stored.
+    let solved = tape.solved();
+    assert_eq!(solved.len(), 4);
+    let node = |node| solved[&Internal { node, output: 0 }][0].clone();
+    assert_eq!(node(0), tsor0(6.0));
+    assert_eq!(node(1), tsor0(36.0));
+    assert_eq!(node(2), tsor0(1.0));
+}
+
+/// Squares like [handlers::Square] while counting how many times it was run.
+struct CountingSquare(Rc<Cell<usize>>);
+
+impl Handler for CountingSquare {
+    fn op(&self) -> OpTy {
+        OpTy::Square
+    }
+
+    fn generate_state(&self, op: &Op, rng: &mut dyn RngCore) -> Vec<Tsor> {
+        Square.generate_state(op, rng)
+    }
+
+    fn forward(&self, imop: ImOp<Native>, state: &[Tsor]) -> Vec<Tsor> {
+        self.0.set(self.0.get() + 1);
+        Square.forward(imop, state)
+    }
+
+    fn backward(
+        &self,
+        imop: ImOp<Native>,
+        state: &[Tsor],
+        output_delta: (usize, Tsor),
+    ) -> (ImOp<Native>, Vec<Tsor>) {
+        Square.backward(imop, state, output_delta)
+    }
+}
+
+#[test]
+fn forward_many_shares_nodes() {
+    let count = Rc::new(Cell::new(0));
+    let backend = Native::new()
+        .handlers(handlers::all())
+        .handler(CountingSquare(count.clone()));
+    let feed = hashmap! {
+        "x".to_owned() => tsor0(3.0),
+        "y".to_owned() => tsor0(1.0),
+    };
+
+    // Two heads which both use the same square.
+    let shared = Tensor::from("x").squared();
+    let first = &shared + &Tensor::from("y");
+    let second = &shared - &Tensor::from("y");
+    assert!(first.shares_graph(&second));
+
+    let graph = second.graph();
+    let state = backend
+        .state(&graph, thread_rng())
+        .expect("unable to generate state");
+    let outputs = backend
+        .forward_many(
+            &graph,
+            &state,
+            &feed,
+            &[first.input().clone(), second.input().clone()],
+        )
+        .expect("unable to forward");
+
+    assert_eq!(outputs, vec![tsor0(10.0), tsor0(8.0)]);
+    assert_eq!(count.get(), 1);
+}
+
+#[test]
+fn accumulate_delta_doubles() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![2], 0.0) - Tensor::from("y")).squared();
+    let graph = loss.graph().clone();
+    let feed = hashmap! {
+        "y".to_owned() => tsor1(&[1.0, 2.0]),
+    };
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let (_, tape) = loss
+        .eval_full(&backend, &state, &feed)
+        .expect("unable to eval");
+    let delta = backend
+        .backward(
+            &graph,
+            &state,
+            &tape,
+            &feed,
+            loss.input().clone(),
+            tsor1(&[1.0, 1.0]),
+        )
+        .expect("unable to backward");
+
+    let mut acc = backend.zero_delta(&graph);
+    backend.accumulate_delta(&mut acc, &delta);
+    backend.accumulate_delta(&mut acc, &delta);
+
+    assert_eq!(delta.table[&0], vec![tsor1(&[-2.0, -4.0])]);
+    assert_eq!(acc.table[&0], vec![tsor1(&[-4.0, -8.0])]);
+}
+
+#[test]
+fn from_graph_manual() {
+    let backend = Native::new().handlers(handlers::all());
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[1.0, 2.0]),
+        "y".to_owned() => tsor1(&[0.5, -1.0]),
+    };
+
+    // Build x^2 + y by hand instead of through the tensor operators.
+    let mut graph = Graph::default();
+    let square = graph.append(Op::Square(Input::from("x"))).unwrap();
+    let sum = graph
+        .append(Op::Add(
+            Input::Internal(Internal {
+                node: square,
+                output: 0,
+            }),
+            Input::from("y"),
+        ))
+        .unwrap();
+    let z = Tensor::from_graph(
+        graph,
+        Input::Internal(Internal {
+            node: sum,
+            output: 0,
+        }),
+    );
+
+    let state = z
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let output = z.eval(&backend, &state, &feed).expect("unable to eval");
+    assert_eq!(output, tsor1(&[1.5, 3.0]));
+}
+
+#[test]
+fn forward_cached_reuses_tape() {
+    let count = Rc::new(Cell::new(0));
+    let backend = Native::new()
+        .handlers(handlers::all())
+        .handler(CountingSquare(count.clone()));
+    let mut feed = hashmap! {
+        "x".to_owned() => tsor1(&[1.0, 2.0]),
+    };
+    let y = Tensor::from("x").squared();
+    let graph = y.graph();
+    let state = y
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let mut cache = None;
+    for _ in 0..2 {
+        let output = backend
+            .forward_cached(&graph, &state, &feed, y.input().clone(), &mut cache)
+            .expect("unable to forward");
+        assert_eq!(output, tsor1(&[1.0, 4.0]));
+    }
+    // The second call used the same feed tensor, so the square wasn't computed again.
+    assert_eq!(count.get(), 1);
+
+    // An equal tensor in a different buffer is still the same input.
+    feed.insert("x".to_owned(), tsor1(&[1.0, 2.0]));
+    backend
+        .forward_cached(&graph, &state, &feed, y.input().clone(), &mut cache)
+        .expect("unable to forward");
+    assert_eq!(count.get(), 1);
+
+    let output = backend
+        .forward_cached(
+            &graph,
+            &state,
+            &hashmap! {
+                "x".to_owned() => tsor1(&[3.0, 2.0]),
+            },
+            y.input().clone(),
+            &mut cache,
+        )
+        .expect("unable to forward");
+    assert_eq!(output, tsor1(&[9.0, 4.0]));
+    assert_eq!(count.get(), 2);
+
+    // A different graph with the same cache doesn't reuse the tape of the square.
+    let z = Tensor::from("x").squared() + Tensor::from("x");
+    let state = z.gen_state(&backend, thread_rng()).unwrap();
+    let output = backend
+        .forward_cached(&z.graph(), &state, &feed, Input::internal(0), &mut cache)
+        .expect("unable to forward");
+    assert_eq!(output, tsor1(&[1.0, 4.0]));
+    assert_eq!(count.get(), 3);
+}
+
+#[test]
+fn forward_cached_transposed_feed() {
+    let backend = Native::new().handlers(handlers::all());
+    let w = tsor2(&[[1.0, 2.0], [3.0, 4.0]]);
+    let y = Tensor::from("w").squared();
+    let graph = y.graph();
+    let state = y
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let mut cache = None;
+    let mut forward = |w: Tsor| {
+        backend
+            .forward_cached(
+                &graph,
+                &state,
+                &hashmap! { "w".to_owned() => w },
+                y.input().clone(),
+                &mut cache,
+            )
+            .expect("unable to forward")
+    };
+    assert_eq!(forward(w.clone()), tsor2(&[[1.0, 4.0], [9.0, 16.0]]));
+    // The transpose shares the buffer and the shape of `w`, but not its strides.
+    assert_eq!(
+        forward(w.reversed_axes()),
+        tsor2(&[[1.0, 9.0], [4.0, 16.0]])
+    );
+}
+
+#[test]
+fn concat_backprop() {
+    let backend = Native::new().handlers(handlers::all());
+    let a = Tensor::train_const(vec![1], 0.0);
+    let b = Tensor::train_const(vec![2], 0.0);
+    let loss = (Tensor::concat(vec![a, b], 0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor1(&[1.0, 2.0, 3.0]),
+    };
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let (output, tape) = loss
+        .eval_full(&backend, &state, &feed)
+        .expect("unable to eval");
+    assert_eq!(output, tsor1(&[1.0, 4.0, 9.0]));
+
+    let delta = backend
+        .backward(
+            &loss.graph(),
+            &state,
+            &tape,
+            &feed,
+            loss.input().clone(),
+            tsor1(&[1.0, 1.0, 1.0]),
+        )
+        .expect("unable to backprop");
+
+    // Each parameter gets the part of the gradient for the elements it contributed.
+    assert_eq!(delta.table[&0][0], tsor1(&[-2.0]));
+    assert_eq!(delta.table[&1][0], tsor1(&[-4.0, -6.0]));
+}
+
+#[test]
+fn substitute_feed_square() {
+    let backend = Native::new().handlers(handlers::all());
+    let feed = hashmap! {
+        "z".to_owned() => tsor1(&[2.0, 3.0]),
+        "y".to_owned() => tsor1(&[1.0, -1.0]),
+    };
+
+    // Wire z^2 into where x was in x * y + x.
+    let block = Tensor::from("x") * Tensor::from("y") + Tensor::from("x");
+    let square = Tensor::from("z").squared();
+    let mut graph = block.graph().clone();
+    let remap = graph.substitute_feed("x", square.graph().clone(), square.input().clone());
+
+    assert!(!graph.feed_keys().contains("x"));
+    assert_eq!(graph.topological_order().map(|order| order.len()), Ok(3));
+
+    let mut output = block.input().clone();
+    output.remap(&remap);
+    let y = Tensor::from_graph(graph, output);
+    let state = y
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let result = y.eval(&backend, &state, &feed).expect("unable to eval");
+    assert_eq!(result, tsor1(&[8.0, 0.0]));
+}
+
+#[test]
+fn train_init_normal_varies() {
+    let backend = Native::new().handlers(handlers::all());
+    let w = Tensor::train_init(
+        vec![4, 8],
+        InitKind::Normal {
+            mean: 0.0,
+            std: 1.0,
+        },
+    );
+
+    let state = w
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let weights = &state[0][0];
+
+    assert_eq!(weights.shape(), &[4, 8]);
+    assert!(weights.iter().any(|&value| value != weights[[0, 0]]));
+}
+
+#[test]
+fn state_is_reproducible() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_init(vec![3, 2], InitKind::Xavier)
+        + Tensor::train_init(
+            vec![3, 2],
+            InitKind::Normal {
+                mean: 1.0,
+                std: 0.5,
+            },
+        )
+        + Tensor::train_const(vec![3, 2], 0.25))
+    .sum();
+
+    let state = |seed| {
+        loss.gen_state(&backend, StdRng::seed_from_u64(seed))
+            .expect("unable to generate state")
+    };
+
+    assert_eq!(state(7), state(7));
+    assert_ne!(state(7), state(8));
+}
+
+#[test]
+fn backward_many_sums_heads() {
+    let backend = Native::new().handlers(handlers::all());
+    let feed = hashmap! {
+        "x".to_owned() => tsor0(3.0),
+    };
+
+    // Both heads share the parameter w = 2, and live in the same graph.
+    let w = Tensor::train_const(vec![], 2.0);
+    let scaled = w.clone() * Tensor::from("x");
+    let squared = w.squared();
+    let graph = w.graph().clone();
+    let state = w
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let mut cache = None;
+    for head in &[&scaled, &squared] {
+        backend
+            .forward_cached(&graph, &state, &feed, head.input().clone(), &mut cache)
+            .expect("unable to eval");
+    }
+    let delta = backend
+        .backward_many(
+            &graph,
+            &state,
+            &cache.unwrap().1,
+            &feed,
+            &[
+                (scaled.input().clone(), tsor0(1.0)),
+                (squared.input().clone(), tsor0(1.0)),
+            ],
+        )
+        .expect("unable to backprop");
+
+    // d(wx)/dw = x = 3 and d(w^2)/dw = 2w = 4.
+    assert_eq!(delta.table[&0], vec![tsor0(7.0)]);
+}
+
+#[test]
+fn dropout_modes() {
+    let x = Tensor::from("x").dropout(0.5);
+    let graph = x.graph().clone();
+    let feed = hashmap! {
+        "x".to_owned() => Tsor::ones(&[64][..]),
+    };
+    let run = |backend: &Native| {
+        let state = x.gen_state(backend, thread_rng()).unwrap();
+        let (eval, _) = backend
+            .forward(&graph, &state, &feed, x.input().clone())
+            .expect("unable to eval");
+        let (train, _) = backend
+            .forward_train(&graph, &state, &feed, x.input().clone())
+            .expect("unable to eval");
+        (eval, train)
+    };
+
+    let backend = Native::new().handlers(handlers::all()).seed(3);
+    let (eval, train) = run(&backend);
+    assert_eq!(eval, feed["x"]);
+    // Kept elements are scaled up so that the expected value doesn't change.
+    assert!(train.iter().all(|&n| n == 0.0 || n == 2.0));
+    assert!(train.iter().any(|&n| n == 0.0));
+    assert!(train.iter().any(|&n| n == 2.0));
+
+    // The masks come from the rng of the backend.
+    let (_, same) = run(&Native::new().handlers(handlers::all()).seed(3));
+    let (_, other) = run(&Native::new().handlers(handlers::all()).seed(4));
+    assert_eq!(train, same);
+    assert_ne!(train, other);
+}
+
+#[test]
+fn dropout_backward_uses_mask() {
+    let backend = Native::new().handlers(handlers::all()).seed(1);
+    let w = Tensor::train_const(vec![32], 1.0);
+    let y = w.dropout(0.5);
+    let graph = y.graph().clone();
+    let feed = hashmap! {};
+    let state = y.gen_state(&backend, thread_rng()).unwrap();
+
+    let (output, internal) = backend
+        .forward_train(&graph, &state, &feed, y.input().clone())
+        .expect("unable to eval");
+    let delta = backend
+        .backward(
+            &graph,
+            &state,
+            &internal,
+            &feed,
+            y.input().clone(),
+            Tsor::ones(&[32][..]),
+        )
+        .expect("unable to backprop");
+    assert_eq!(delta.table[&0], vec![output]);
+}
+
+#[test]
+fn forward_stochastic_reproducible() {
+    let backend = Native::new().handlers(handlers::all());
+    let x = Tensor::from("x").dropout(0.3);
+    let graph = x.graph().clone();
+    let feed = hashmap! {
+        "x".to_owned() => Tsor::ones(&[4, 16][..]),
+    };
+    let state = x.gen_state(&backend, thread_rng()).unwrap();
+    let mask = |seed| {
+        let (output, _) = backend
+            .forward_stochastic(
+                &graph,
+                &state,
+                &feed,
+                x.input().clone(),
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .expect("unable to eval");
+        output
+    };
+
+    assert_eq!(mask(11), mask(11));
+    assert_ne!(mask(11), mask(12));
+}
+
+#[test]
+fn batch_norm_running_statistics() {
+    let backend = Native::new().handlers(handlers::all());
+    let y = Tensor::from("x").batch_norm(
+        Tensor::train_const(vec![2], 1.0),
+        Tensor::train_const(vec![2], 0.0),
+    );
+    let graph = y.graph().clone();
+    let node = 2;
+    let feed = hashmap! {
+        "x".to_owned() => tsor2(&[[1.0, 10.0], [3.0, 30.0]]),
+    };
+    let mut state = y.gen_state(&backend, thread_rng()).unwrap();
+
+    let (output, internal) = backend
+        .forward_train(&graph, &state, &feed, y.input().clone())
+        .expect("unable to eval");
+    assert!(output.iter().all(|n| (n.abs() - 1.0).abs() < 1e-3));
+    backend
+        .update_state(&graph, &mut state, &internal)
+        .expect("unable to update state");
+    // The batch mean is [2, 20] and its variance [1, 100], each with a momentum of 0.1.
+    assert_eq!(state[node][0], tsor1(&[0.2, 2.0]));
+    assert_eq!(state[node][1], tsor1(&[1.0, 10.9]));
+
+    let running = state[node].clone();
+    let (_, internal) = backend
+        .forward(&graph, &state, &feed, y.input().clone())
+        .expect("unable to eval");
+    backend
+        .update_state(&graph, &mut state, &internal)
+        .expect("unable to update state");
+    assert_eq!(state[node], running);
+}
+
+#[test]
+fn batch_norm_backward() {
+    let backend = Native::new().handlers(handlers::all());
+    let x = Tensor::train_init(
+        vec![4, 3],
+        InitKind::Uniform {
+            low: -1.0,
+            high: 1.0,
+        },
+    );
+    let gamma = Tensor::train_init(
+        vec![3],
+        InitKind::Uniform {
+            low: 0.5,
+            high: 1.5,
+        },
+    );
+    let beta = Tensor::train_const(vec![3], 0.1);
+    let loss = (x.batch_norm(gamma, beta) * Tensor::from("w")).sum();
+    let graph = loss.graph().clone();
+    let feed = hashmap! {
+        "w".to_owned() => tsor2(&[
+            [1.0, -2.0, 0.5],
+            [0.3, 1.0, -1.0],
+            [-0.7, 0.2, 2.0],
+            [1.5, 0.4, -0.3],
+        ]),
+    };
+    let state = loss.gen_state(&backend, StdRng::seed_from_u64(5)).unwrap();
+    let eval = |state: &Vec<Vec<Tsor>>| {
+        let (output, _) = backend
+            .forward_train(&graph, state, &feed, loss.input().clone())
+            .expect("unable to eval");
+        output.sum()
+    };
+
+    let (_, internal) = backend
+        .forward_train(&graph, &state, &feed, loss.input().clone())
+        .unwrap();
+    let delta = backend
+        .backward(
+            &graph,
+            &state,
+            &internal,
+            &feed,
+            loss.input().clone(),
+            tsor0(1.0),
+        )
+        .unwrap();
+
+    let epsilon = 1e-2;
+    for node in 0..3 {
+        for j in 0..state[node][0].len() {
+            let nudged = |amount: f32| {
+                let mut state = state.clone();
+                *state[node][0].iter_mut().nth(j).unwrap() += amount;
+                eval(&state)
+            };
+            let numeric = (nudged(epsilon) - nudged(-epsilon)) / (2.0 * epsilon);
+            let analytic = *delta.table[&node][0].iter().nth(j).unwrap();
+            assert!(
+                (numeric - analytic).abs() < 1e-2,
+                "{} {}",
+                numeric,
+                analytic
+            );
+        }
+    }
+}
+
+#[test]
+fn map_ops_square_to_mul() {
+    let backend = Native::new().handlers(handlers::all());
+    let y = (Tensor::from("x") - Tensor::scalar(1.0)).squared();
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[-2.0, 0.5, 3.0]),
+    };
+    let state = y.gen_state(&backend, thread_rng()).unwrap();
+    let eval = |graph: &Graph| {
+        backend
+            .forward(graph, &state, &feed, y.input().clone())
+            .map(|(output, _)| output)
+            .expect("unable to eval")
+    };
+
+    let mut graph = y.graph().clone();
+    let expected = eval(&graph);
+    graph.map_ops(|_, op| {
+        if let Op::Square(x) = op {
+            *op = Op::Mul(x.clone(), x.clone());
+        }
+    });
+    assert!(graph.ops().iter().all(|op| !matches!(op, Op::Square(_))));
+    let sub = Input::Internal(Internal { node: 1, output: 0 });
+    assert_eq!(graph.ops()[2], Op::Mul(sub.clone(), sub));
+    assert_eq!(eval(&graph), expected);
+}
+
+#[test]
+fn fuse_squared_diff() {
+    let backend = Native::new().handlers(handlers::all());
+    let y = (Tensor::from("a") - Tensor::from("b")).squared().sum();
+    let feed = hashmap! {
+        "a".to_owned() => tsor1(&[1.0, 2.0, 3.0]),
+        "b".to_owned() => tsor1(&[3.0, 2.0, -1.0]),
+    };
+    let state = y.gen_state(&backend, thread_rng()).unwrap();
+
+    let mut graph = y.graph().clone();
+    let remap = graph.fuse();
+    assert_eq!(graph.node_count(), y.graph().node_count() - 1);
+    assert_eq!(
+        graph.ops()[0],
+        Op::SquaredDiff(Input::from("a"), Input::from("b"))
+    );
+
+    let mut output = y.input().clone();
+    output.remap(&remap);
+    // The sub had no state, so dropping its entry keeps the state aligned with the nodes.
+    let mut fused_state = state.clone();
+    fused_state.remove(0);
+    let (fused, _) = backend
+        .forward(&graph, &fused_state, &feed, output)
+        .expect("unable to eval");
+    assert_eq!(fused, y.eval(&backend, &state, &feed).unwrap());
+    assert_eq!(fused, tsor0(20.0));
+}
+
+#[test]
+fn train_logged_norms() {
+    let backend = Native::new().handlers(handlers::all());
+    let a = Tensor::train_const(vec![2], 0.0);
+    let b = Tensor::train_const(vec![2], 1.0);
+    let loss = ((a + b) - Tensor::from("target")).squared().sum();
+    let feed = hashmap! {
+        "target".to_owned() => tsor1(&[3.0, 4.0]),
+    };
+    let mut state = loss.gen_state(&backend, thread_rng()).unwrap();
+    let (_, internal) = loss.eval_full(&backend, &state, &feed).unwrap();
+    let delta = backend
+        .backward(
+            &loss.graph(),
+            &state,
+            &internal,
+            &feed,
+            loss.input().clone(),
+            tsor0(1.0),
+        )
+        .unwrap();
+
+    let norms = backend
+        .train_logged(&loss.graph(), &mut state, &delta)
+        .unwrap();
+    let mut nodes: Vec<_> = norms.keys().copied().collect();
+    nodes.sort_unstable();
+    assert_eq!(nodes, vec![0, 1]);
+    // Both deltas are 2 * (a + b - target) = [-4, -6].
+    assert!(norms.values().all(|&norm| norm > 0.0));
+    assert!((norms[&0] - 52f32.sqrt()).abs() < 1e-5, "got {}", norms[&0]);
+    assert_eq!(norms[&0], norms[&1]);
+    assert_eq!(state[1], vec![tsor1(&[-3.0, -5.0])]);
+}
+
+#[test]
+fn state_dict_edits_parameter() {
+    let backend = Native::new().handlers(handlers::all());
+    let y = Tensor::train_const(vec![2], 1.0) * Tensor::from("x");
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[2.0, 3.0]),
+    };
+    let mut state = y.gen_state(&backend, thread_rng()).unwrap();
+    assert_eq!(y.eval(&backend, &state, &feed).unwrap(), tsor1(&[2.0, 3.0]));
+
+    // Only the trainable constant is a parameter.
+    let dict = backend.state_dict(&y.graph(), &state);
+    assert_eq!(dict.len(), 1);
+    assert_eq!(dict[&0], &tsor1(&[1.0, 1.0]));
+
+    *backend
+        .state_dict_mut(&y.graph(), &mut state)
+        .remove(&0)
+        .unwrap() = tsor1(&[-1.0, 0.5]);
+    assert_eq!(
+        y.eval(&backend, &state, &feed).unwrap(),
+        tsor1(&[-2.0, 1.5])
+    );
+}
+
+#[test]
+fn state_dict_skips_buffers() {
+    let backend = Native::new().handlers(handlers::all());
+    let gamma = Tensor::train_const(vec![2], 1.0);
+    let beta = Tensor::train_const(vec![2], 0.0);
+    let y = Tensor::from("x").batch_norm(gamma, beta);
+    let state = y.gen_state(&backend, thread_rng()).unwrap();
+
+    // The running statistics of the batch norm are state, but not parameters.
+    let mut nodes: Vec<_> = backend.state_dict(&y.graph(), &state).into_keys().collect();
+    nodes.sort_unstable();
+    assert_eq!(nodes, vec![0, 1]);
+    assert_eq!(state[2].len(), 2);
+}
+
+#[test]
+fn transfer_state_identical_graph() {
+    let backend = Native::new().handlers(handlers::all());
+    let build = || {
+        let uniform = InitKind::Uniform {
+            low: -1.0,
+            high: 1.0,
+        };
+        Tensor::train_init(vec![2], uniform.clone()) * Tensor::from("x")
+            + Tensor::train_init(vec![2], uniform)
+    };
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[2.0, 3.0]),
+    };
+    let src = build();
+    let src_state = src.gen_state(&backend, StdRng::seed_from_u64(1)).unwrap();
+
+    let dst = build();
+    let dst_state = backend
+        .transfer_state(
+            &src.graph(),
+            &src_state,
+            &dst.graph(),
+            StdRng::seed_from_u64(2),
+        )
+        .unwrap();
+    assert_eq!(dst_state, src_state);
+    assert_eq!(
+        dst.eval(&backend, &dst_state, &feed).unwrap(),
+        src.eval(&backend, &src_state, &feed).unwrap()
+    );
+
+    // Parameters whose shapes don't match keep their fresh initialization.
+    let wider =
+        Tensor::train_const(vec![3], 5.0) + Tensor::train_init(vec![2], InitKind::Const(7.0));
+    let wider_state = backend
+        .transfer_state(&src.graph(), &src_state, &wider.graph(), thread_rng())
+        .unwrap();
+    assert_eq!(wider_state[0], vec![tsor1(&[5.0, 5.0, 5.0])]);
+    assert_eq!(wider_state[1], src_state[2]);
+}
+
+#[test]
+fn stack_backprop() {
+    let backend = Native::new().handlers(handlers::all());
+    let a = Tensor::train_const(vec![2], 1.0);
+    let b = Tensor::train_const(vec![2], 2.0);
+    let loss = (Tensor::stack(vec![a, b], 1) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor2(&[[0.0, 0.0], [1.0, 3.0]]),
+    };
+    let state = loss.gen_state(&backend, thread_rng()).unwrap();
+    let (output, tape) = loss.eval_full(&backend, &state, &feed).unwrap();
+    // The new axis is the second one, so each parameter is a column.
+    assert_eq!(output, tsor2(&[[1.0, 4.0], [0.0, 1.0]]));
+
+    let delta = backend
+        .backward(
+            &loss.graph(),
+            &state,
+            &tape,
+            &feed,
+            loss.input().clone(),
+            tsor2(&[[1.0, 1.0], [1.0, 1.0]]),
+        )
+        .unwrap();
+    assert_eq!(delta.table[&0], vec![tsor1(&[2.0, 0.0])]);
+    assert_eq!(delta.table[&1], vec![tsor1(&[4.0, -2.0])]);
+}
+
+#[test]
+fn scalar_on_the_left_forward_backward() {
+    let backend = Native::new().handlers(handlers::all());
+    let x = || Tensor::train_const(vec![2], 3.0);
+    let feed = hashmap! {};
+
+    // The scalar constant is node 0 and the tensor is merged after it as node 1.
+    for (y, value, grad) in [
+        (2.0 * x(), 6.0, 2.0),
+        (1.0 + x(), 4.0, 1.0),
+        (1.0 - x(), -2.0, -1.0),
+    ] {
+        let state = y.gen_state(&backend, thread_rng()).unwrap();
+        let (output, internal) = y.eval_full(&backend, &state, &feed).unwrap();
+        assert_eq!(output, tsor1(&[value, value]));
+
+        let delta = backend
+            .backward(
+                &y.graph(),
+                &state,
+                &internal,
+                &feed,
+                y.input().clone(),
+                tsor1(&[1.0, 1.0]),
+            )
+            .unwrap();
+        assert_eq!(delta.table[&1], vec![tsor1(&[grad, grad])]);
+    }
+}
+
+#[test]
+fn broadcast_backward_reduces_to_operand() {
+    let backend = Native::new().handlers(handlers::all());
+    let row = Tensor::train_const(vec![1, 2], 1.0);
+    let y = (Tensor::from("x") * row).sum();
+    let feed = hashmap! {
+        "x".to_owned() => tsor2(&[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]),
+    };
+    let state = y.gen_state(&backend, thread_rng()).unwrap();
+    let (output, internal) = y.eval_full(&backend, &state, &feed).unwrap();
+    assert_eq!(output, tsor0(21.0));
+
+    let delta = backend
+        .backward(
+            &y.graph(),
+            &state,
+            &internal,
+            &feed,
+            y.input().clone(),
+            tsor0(1.0),
+        )
+        .unwrap();
+    // The row was stretched over the 3 rows of `x`, so its delta sums their columns.
+    assert_eq!(delta.table[&0], vec![tsor2(&[[9.0, 12.0]])]);
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "serde")]
+
+use deep::*;
+use deep_native::optimizers::Sgd;
+use deep_native::*;
+use maplit::hashmap;
+use rand::thread_rng;
+
+#[test]
+fn save_load_round_trip() {
+    let backend = Native::new().handlers(handlers::all());
+    let w = Tensor::train_init(
+        vec![2],
+        InitKind::Uniform {
+            low: -1.0,
+            high: 1.0,
+        },
+    );
+    let y = w * Tensor::from("x");
+    let loss = Tensor::mse(y.clone(), Tensor::from("target"));
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[1.0, 2.0]),
+        "target".to_owned() => tsor1(&[1.0, -1.0]),
+    };
+    let mut state = loss.gen_state(&backend, thread_rng()).unwrap();
+    for _ in 0..5 {
+        loss.gradient_descent(
+            &backend,
+            &mut state,
+            &feed,
+            &mut Sgd::new(0.1),
+            |t| t.sum(),
+            tsor0,
+        )
+        .expect("unable to train");
+    }
+
+    let bytes = backend.save_state(&state).expect("unable to save");
+    let loaded = backend.load_state(&bytes).expect("unable to load");
+    assert_eq!(loaded, state);
+    assert_eq!(
+        y.eval(&backend, &loaded, &feed).unwrap(),
+        y.eval(&backend, &state, &feed).unwrap()
+    );
+
+    assert!(backend.load_state(&bytes[..bytes.len() - 1]).is_err());
+}
@@ -0,0 +1,95 @@
+use deep::*;
+use deep_native::optimizers::Sgd;
+use deep_native::*;
+use maplit::hashmap;
+use rand::thread_rng;
+
+#[test]
+fn cross_entropy_eval() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = Tensor::cross_entropy(Tensor::from("logits"), Tensor::from("targets"));
+
+    // Log softmax, multiply, sum and negate.
+    assert_eq!(loss.graph().ops().len(), 4);
+    assert!(matches!(&loss.graph().ops()[0], Op::LogSoftmax(_, 1)));
+    assert!(matches!(&loss.graph().ops()[3], Op::Neg(..)));
+
+    let feed = hashmap! {
+        "logits".to_owned() => tsor2(&[[0.0, 0.0], [1000.0, 0.0]]),
+        "targets".to_owned() => tsor2(&[[1.0, 0.0], [1.0, 0.0]]),
+    };
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let output = loss.eval(&backend, &state, &feed).expect("unable to eval");
+
+    // The first sample is a coin flip and the second one is certain, even with huge logits.
+    let expected = 2.0f32.ln();
+    assert!((output.sum() - expected).abs() < 1e-6, "got {}", output);
+}
+
+#[test]
+fn cross_entropy_gradient() {
+    let backend = Native::new().handlers(handlers::all());
+    let logits = Tensor::train_const(vec![1, 3], 0.0);
+    let loss = Tensor::cross_entropy(logits, Tensor::from("targets"));
+    let feed = hashmap! {
+        "targets".to_owned() => tsor2(&[[0.0, 1.0, 0.0]]),
+    };
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let (_, tape) = loss
+        .eval_full(&backend, &state, &feed)
+        .expect("unable to eval");
+    let delta = backend
+        .backward(
+            &loss.graph(),
+            &state,
+            &tape,
+            &feed,
+            loss.input().clone(),
+            tsor0(1.0),
+        )
+        .expect("unable to backprop");
+
+    // The gradient of cross-entropy with respect to the logits is softmax - targets.
+    let third = 1.0 / 3.0;
+    let expected = tsor2(&[[third, third - 1.0, third]]);
+    let grad = &delta.table[&0][0];
+    assert!(grad
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+}
+
+#[test]
+fn mse_decreases() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = Tensor::mse(Tensor::train_const(vec![3], 0.0), Tensor::from("target"));
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let feed = hashmap! {
+        "target".to_owned() => tsor1(&[1.0, -2.0, 3.0]),
+    };
+
+    let losses: Vec<f32> = (0..20)
+        .map(|_| {
+            loss.gradient_descent(
+                &backend,
+                &mut state,
+                &feed,
+                &mut Sgd::new(0.1),
+                |t| t.sum(),
+                tsor0,
+            )
+            .expect("unable to train")
+        })
+        .collect();
+
+    // The loss starts at (1 + 4 + 9) / 3 and must go down at every step.
+    assert!((losses[0] - 14.0 / 3.0).abs() < 1e-6);
+    assert!(losses.windows(2).all(|w| w[1] < w[0]));
+    assert!(losses[19] < 0.5);
+}
@@ -0,0 +1,327 @@
+use deep::*;
+use deep_backend_tools::AccumulateTensors;
+use deep_native::optimizers::{Adam, ClipGradNorm, ClippableDelta, Sgd, SgdMomentum};
+use deep_native::*;
+use maplit::hashmap;
+use rand::thread_rng;
+
+#[test]
+fn sgd_matches_inline_update() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![2], 1.0) - Tensor::from("y")).squared();
+    let graph = loss.graph().clone();
+    let feed = hashmap! {
+        "y".to_owned() => tsor1(&[3.0, -2.0]),
+    };
+    let learning_rate = 0.1;
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    // The old inline path: scale the output delta by the learning rate and train with it.
+    let mut inline_state = state.clone();
+    let (output, internal) = backend
+        .forward(&graph, &inline_state, &feed, loss.input().clone())
+        .expect("unable to forward");
+    let output_delta = tsor0(-learning_rate * output.sum());
+    let delta = backend
+        .backward(
+            &graph,
+            &inline_state,
+            &internal,
+            &feed,
+            loss.input().clone(),
+            output_delta,
+        )
+        .expect("unable to backward");
+    backend
+        .train(&mut inline_state, &delta)
+        .expect("unable to train");
+
+    // The optimizer path.
+    let mut sgd_state = state;
+    loss.gradient_descent(
+        &backend,
+        &mut sgd_state,
+        &feed,
+        &mut Sgd::new(learning_rate),
+        |t| t.sum(),
+        tsor0,
+    )
+    .expect("unable to train");
+
+    for (a, b) in inline_state[0][0].iter().zip(sgd_state[0][0].iter()) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+}
+
+#[test]
+fn adam_moments_across_steps() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![], 0.0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor0(1.0),
+    };
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let mut adam = Adam::new(0.1);
+    let step = |adam: &mut Adam, state: &mut _| {
+        loss.gradient_descent(&backend, state, &feed, adam, |t| t.sum(), tsor0)
+            .expect("unable to train")
+    };
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+
+    // The loss is 1 and the delta for the parameter is 2 * (0 - 1) * 1 = -2.
+    step(&mut adam, &mut state);
+    assert_eq!(adam.steps, 1);
+    assert!(close(adam.first_moments[&0][0].sum(), 0.1 * -2.0));
+    assert!(close(adam.second_moments[&0][0].sum(), 0.001 * 4.0));
+    // The first bias corrected step has the size of the learning rate.
+    assert!(close(state[0][0].sum(), 0.1));
+
+    // Now the loss is 0.81 and the delta 2 * (0.1 - 1) * 0.81 = -1.458.
+    step(&mut adam, &mut state);
+    assert_eq!(adam.steps, 2);
+    assert!(close(
+        adam.first_moments[&0][0].sum(),
+        0.9 * -0.2 + 0.1 * -1.458
+    ));
+    assert!(close(
+        adam.second_moments[&0][0].sum(),
+        0.999 * 0.004 + 0.001 * 1.458 * 1.458
+    ));
+}
+
+#[test]
+fn momentum_converges_faster() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![], 0.0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor0(2.0),
+    };
+
+    let final_loss = |optimizer: &mut dyn Optimizer<Native>| {
+        let mut state = loss
+            .gen_state(&backend, thread_rng())
+            .expect("unable to generate state");
+        let mut value = 0.0;
+        for _ in 0..20 {
+            value = loss
+                .gradient_descent(&backend, &mut state, &feed, optimizer, |t| t.sum(), tsor0)
+                .expect("unable to train");
+        }
+        value
+    };
+
+    let plain = final_loss(&mut Sgd::new(0.01));
+    let momentum = final_loss(&mut SgdMomentum::new(0.01, 0.5, 0.0));
+    assert!(momentum < plain, "{} >= {}", momentum, plain);
+}
+
+#[test]
+fn clip_grad_norm() {
+    let delta = |a: f32, b: f32| AccumulateTensors {
+        table: hashmap! {
+            0 => vec![tsor1(&[a])],
+            3 => vec![tsor1(&[b])],
+        },
+    };
+
+    // A norm of 5 is scaled down to 1.
+    let mut large = delta(3.0, 4.0);
+    large.clip_grad_norm(1.0);
+    assert!((large.global_norm() - 1.0).abs() < 1e-6);
+    assert!((large.table[&0][0][0] - 0.6).abs() < 1e-6);
+    assert!((large.table[&3][0][0] - 0.8).abs() < 1e-6);
+
+    // A norm of 0.5 is left alone.
+    let mut small = delta(0.3, 0.4);
+    small.clip_grad_norm(1.0);
+    assert_eq!(small.table[&0][0], tsor1(&[0.3]));
+    assert_eq!(small.table[&3][0], tsor1(&[0.4]));
+}
+
+#[test]
+fn clip_grad_norm_optimizer() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![], 0.0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor0(10.0),
+    };
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    // The delta is 2 * (0 - 10) * 100 = -2000, but the step is limited to the clipped norm.
+    let mut optimizer = ClipGradNorm::new(1.0, Sgd::new(0.5));
+    loss.gradient_descent(
+        &backend,
+        &mut state,
+        &feed,
+        &mut optimizer,
+        |t| t.sum(),
+        tsor0,
+    )
+    .expect("unable to train");
+    assert!((state[0][0].sum() - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn gradient_descent_returns_delta() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![], 0.0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor0(1.0),
+    };
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let (loss_value, delta) = loss
+        .gradient_descent_with_delta(
+            &backend,
+            &mut state,
+            &feed,
+            &mut Sgd::new(0.1),
+            |t| t.sum(),
+            tsor0,
+        )
+        .expect("unable to train");
+
+    assert_eq!(loss_value, 1.0);
+    // The trainable constant receives the gradient 2 * (0 - 1) * 1.
+    assert!(!delta.table.is_empty());
+    assert_eq!(delta.table[&0], vec![tsor0(-2.0)]);
+}
+
+#[test]
+fn gradient_descent_scheduled_sets_rate() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = Tensor::train_const(vec![], 0.0).sum();
+    let feed = hashmap! {};
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+    let schedule = StepDecay {
+        base: 1.0,
+        gamma: 0.5,
+        step_size: 2,
+    };
+
+    // Seeding with a delta of 1 makes the gradient of the parameter 1, so every step moves it by
+    // the learning rate.
+    let mut sgd = Sgd::new(0.0);
+    let mut values = vec![];
+    for step in 0..4 {
+        loss.gradient_descent_scheduled(
+            &backend,
+            &mut state,
+            &feed,
+            &mut sgd,
+            &schedule,
+            step,
+            |_| 1.0,
+            tsor0,
+        )
+        .expect("unable to train");
+        values.push(state[0][0].sum());
+    }
+    assert_eq!(values, vec![-1.0, -2.0, -2.5, -3.0]);
+    assert_eq!(sgd.learning_rate, 0.5);
+}
+
+#[test]
+fn sgd_groups() {
+    let backend = Native::new().handlers(handlers::all());
+    // The backbone ends up as node 0 and the head as node 1 of the merged graph.
+    let loss = (Tensor::train_const(vec![], 0.0) + Tensor::train_const(vec![], 0.0)).sum();
+    let (backbone, head) = (0, 1);
+    let feed = hashmap! {};
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let mut sgd = Sgd::new(0.01).group(vec![head], 0.5);
+    loss.gradient_descent(&backend, &mut state, &feed, &mut sgd, |_| 1.0, tsor0)
+        .expect("unable to train");
+
+    // Both parameters receive a delta of 1.
+    assert_eq!(state[backbone][0], tsor0(-0.01));
+    assert_eq!(state[head][0], tsor0(-0.5));
+}
+
+#[test]
+fn frozen_parameter_unchanged() {
+    let backend = Native::new().handlers(handlers::all());
+    let frozen = Tensor::train_const(vec![], 1.0);
+    frozen.freeze();
+    // The frozen parameter ends up as node 0 and the trainable one as node 1.
+    let loss = (frozen + Tensor::train_const(vec![], 1.0)).sum();
+    let feed = hashmap! {};
+    let mut state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let (_, delta) = loss
+        .gradient_descent_with_delta(
+            &backend,
+            &mut state,
+            &feed,
+            &mut Sgd::new(0.5),
+            |_| 1.0,
+            tsor0,
+        )
+        .expect("unable to train");
+
+    assert!(!delta.table.contains_key(&0));
+    assert_eq!(state[0][0], tsor0(1.0));
+    assert_eq!(state[1][0], tsor0(0.5));
+
+    // A delta built by hand for the frozen node doesn't change it either.
+    let mut delta = backend.zero_delta(&loss.graph());
+    delta.table.insert(0, vec![tsor0(3.0)]);
+    delta.table.insert(1, vec![tsor0(3.0)]);
+    backend
+        .train_unfrozen(&loss.graph(), &mut state, &delta)
+        .expect("unable to train");
+    assert_eq!(state[0][0], tsor0(1.0));
+    assert_eq!(state[1][0], tsor0(3.5));
+}
+
+#[test]
+fn gradient_descent_logged_norms() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![], 1.0) - Tensor::from("y")).squared();
+    let feed = hashmap! {
+        "y".to_owned() => tsor0(3.0),
+    };
+
+    // The delta is 2 * (1 - 3) = -4, so plain SGD steps by 2 and the clipped one by 0.5.
+    let mut state = loss.gen_state(&backend, thread_rng()).unwrap();
+    let (_, norms) = loss
+        .gradient_descent_logged(
+            &backend,
+            &mut state,
+            &feed,
+            &mut Sgd::new(0.5),
+            |_| 1.0,
+            tsor0,
+        )
+        .expect("unable to train");
+    assert_eq!(norms, hashmap! { 0 => 2.0 });
+    assert_eq!(state[0][0], tsor0(3.0));
+
+    let mut state = loss.gen_state(&backend, thread_rng()).unwrap();
+    let (_, norms) = loss
+        .gradient_descent_logged(
+            &backend,
+            &mut state,
+            &feed,
+            &mut ClipGradNorm::new(1.0, Sgd::new(0.5)),
+            |_| 1.0,
+            tsor0,
+        )
+        .expect("unable to train");
+    assert_eq!(norms, hashmap! { 0 => 0.5 });
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "testing")]
+
+use deep::*;
+use deep_native::testing::grad_check;
+use deep_native::*;
+use maplit::hashmap;
+use rand::thread_rng;
+
+#[test]
+fn grad_check_square() {
+    let backend = Native::new().handlers(handlers::all());
+    let loss = (Tensor::train_const(vec![3], 0.5) * Tensor::from("x")).squared();
+    let feed = hashmap! {
+        "x".to_owned() => tsor1(&[1.0, -2.0, 3.0]),
+    };
+    let state = loss
+        .gen_state(&backend, thread_rng())
+        .expect("unable to generate state");
+
+    let error = grad_check(&backend, &loss, &state, &feed, 1e-2).expect("unable to check");
+    assert!(error < 1e-3, "relative error {} is too large", error);
+}
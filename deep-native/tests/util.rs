@@ -0,0 +1,60 @@
+use deep::*;
+use deep_native::util::{broadcast_grad, broadcast_pair, reduce_grad};
+use deep_native::*;
+use maplit::hashmap;
+use rand::thread_rng;
+
+#[test]
+fn broadcast_grad_shape() {
+    assert_eq!(broadcast_grad(&[3], 0.5), tsor1(&[0.5, 0.5, 0.5]));
+    assert_eq!(broadcast_grad(&[], 2.0), tsor0(2.0));
+    assert_eq!(
+        broadcast_grad(&[2, 2], 1.0),
+        tsor2(&[[1.0, 1.0], [1.0, 1.0]])
+    );
+}
+
+#[test]
+fn sum_distributes_delta() {
+    let backend = Native::new().handlers(handlers::all());
+    let w = Tensor::train_const(vec![3], 1.0);
+    let sum = w.sum();
+    let mean = w.mean();
+
+    for (loss, expected) in &[(sum, tsor1(&[3.0; 3])), (mean, tsor1(&[1.0; 3]))] {
+        let graph = loss.graph();
+        let state = loss
+            .gen_state(&backend, thread_rng())
+            .expect("unable to generate state");
+        let feed = hashmap! {};
+        let (_, tape) = loss
+            .eval_full(&backend, &state, &feed)
+            .expect("unable to eval");
+        // Every element of `w` contributed to the output, so all of them get the delta (divided
+        // by the number of elements for the mean).
+        let delta = backend
+            .backward(
+                &graph,
+                &state,
+                &tape,
+                &feed,
+                loss.input().clone(),
+                tsor0(3.0),
+            )
+            .expect("unable to backprop");
+        assert_eq!(&delta.table[&0][0], expected);
+    }
+}
+
+#[test]
+fn reduce_grad_to_operand() {
+    let grad = tsor2(&[[1.0, 2.0], [3.0, 4.0]]);
+    assert_eq!(reduce_grad(grad.clone(), &[]), tsor0(10.0));
+    assert_eq!(reduce_grad(grad.clone(), &[2]), tsor1(&[4.0, 6.0]));
+    assert_eq!(reduce_grad(grad.clone(), &[2, 1]), tsor2(&[[3.0], [7.0]]));
+    assert_eq!(reduce_grad(grad.clone(), &[2, 2]), grad);
+
+    let (a, b) = broadcast_pair(tsor0(2.0), tsor1(&[1.0, 2.0]));
+    assert_eq!(a, tsor1(&[2.0, 2.0]));
+    assert_eq!(b, tsor1(&[1.0, 2.0]));
+}
@@ -1,14 +1,30 @@
 #[macro_use]
 extern crate strum_macros;
 
+mod optimizer;
+mod schedule;
+pub mod shapes;
+mod state_dict;
 mod tensor;
+mod visitor;
 
+pub use optimizer::Optimizer;
+pub use schedule::{Constant, ExponentialDecay, Schedule, StepDecay};
+pub use state_dict::StateDict;
 pub use tensor::Tensor;
+pub use visitor::GraphVisitor;
 
 use rand_core::RngCore;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::AddAssign;
 
 /// References a tensor which is produced as an output of an operation stored in the graph
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Internal {
     /// The result of which [Op] the tensor is.
     pub node: usize,
@@ -20,15 +36,261 @@ impl Internal {
     fn shift_inputs(&mut self, shift: usize) {
         self.node += shift;
     }
+
+    /// Rewrites the node through a map from old to new node indices, like the ones returned by
+    /// [Graph::dedup] and [Graph::prune].
+    ///
+    /// Panics if the node is not in the map, which means it was removed.
+    pub fn remap(&mut self, map: &HashMap<usize, usize>) {
+        self.node = *map
+            .get(&self.node)
+            .unwrap_or_else(|| panic!("node {} was removed from the graph", self.node));
+    }
 }
 
-#[derive(Clone, Debug, EnumDiscriminants)]
+#[derive(Clone, Debug, PartialEq, EnumDiscriminants)]
 #[strum_discriminants(name(OpTy), derive(Hash))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Op {
     Add(Input, Input),
     Sub(Input, Input),
+    Mul(Input, Input),
+    /// Divides the first input by the second.
+    ///
+    /// The backend is responsible for what happens when the denominator is zero. It may produce
+    /// NaN/infinity as IEEE floats do, or it may refuse, in which case the failure should be
+    /// surfaced through [Backend::Error].
+    Div(Input, Input),
     Square(Input),
+    Neg(Input),
+    Exp(Input),
+    /// Natural logarithm of the input.
+    ///
+    /// The logarithm of non-positive values is undefined. The backend decides whether to produce
+    /// NaN or to fail with [Backend::Error].
+    Log(Input),
+    /// Square root of the input.
+    ///
+    /// The square root of negative values is undefined. The backend decides whether to produce NaN
+    /// or to fail with [Backend::Error].
+    Sqrt(Input),
+    Tanh(Input),
+    Sigmoid(Input),
+    Relu(Input),
+    /// Like [Op::Relu], but clamps everything below the threshold to the threshold instead of zero.
+    ReluThreshold(Input, f64),
+    /// Raises the input to a fixed exponent.
+    Pow(Input, f64),
+    /// Elementwise maximum of the two inputs.
+    ///
+    /// When both inputs are equal the gradient could go to either of them, so the tie-breaking rule
+    /// of the backward pass is chosen by the backend.
+    Max(Input, Input),
+    /// Elementwise minimum of the two inputs.
+    ///
+    /// When both inputs are equal the gradient could go to either of them, so the tie-breaking rule
+    /// of the backward pass is chosen by the backend.
+    Min(Input, Input),
+    /// Sums every element of the input, reducing it to a scalar.
+    Sum(Input),
+    /// Averages every element of the input, reducing it to a scalar.
+    ///
+    /// This always reduces over the whole tensor; reducing along specific axes is not covered by
+    /// this op.
+    Mean(Input),
+    /// Sums the input along a single axis.
+    ///
+    /// Whether the reduced axis is kept with a size of 1 or dropped is decided by the backend.
+    SumAxis(Input, usize),
+    /// Changes the shape of the input without changing its elements.
+    ///
+    /// The backend validates that the new shape has the same number of elements as the input and
+    /// otherwise fails with [Backend::Error].
+    Reshape(Input, Vec<usize>),
+    /// Swaps two axes of the input.
+    ///
+    /// Referring to an axis the input doesn't have is a backend error.
+    Transpose(Input, usize, usize),
+    /// Matrix product of the first input by the second.
+    ///
+    /// Both inputs are expected to be 2D: an `(n, k)` matrix times a `(k, m)` matrix produces an
+    /// `(n, m)` matrix. The product is not commutative, so the order of the inputs matters.
+    MatMul(Input, Input),
+    /// A constant tensor of the given shape with every element set to the value.
+    ///
+    /// Unlike [Op::TrainConst] it is not trainable, so backends never produce a delta for it.
+    Const(Vec<usize>, f64),
+    /// Splits the input along its first axis at `index`, producing two outputs.
+    ///
+    /// Output `0` holds the rows before `index` and output `1` holds the rest of them.
+    SplitAt(Input, usize),
+    /// Limits every element of the input to the range from the first to the second value.
+    ///
+    /// The backward pass zeroes the gradient of elements which were outside of the range, since
+    /// changing them slightly doesn't change the output.
+    Clamp(Input, f64, f64),
+    /// Absolute value of the input.
+    ///
+    /// The absolute value has no derivative at zero. Backends should use a subgradient of `0`
+    /// there, so that results are reproducible across backends.
+    Abs(Input),
+    /// Softmax of the input along an axis, so the elements along it are positive and sum to one.
+    ///
+    /// Exponentiating large values overflows, so backends must subtract the maximum along the axis
+    /// before exponentiating, which doesn't change the result.
+    Softmax(Input, usize),
+    /// Logarithm of the softmax of the input along an axis.
+    ///
+    /// Like [Op::Softmax], backends must subtract the maximum along the axis before exponentiating.
+    /// Computing it fused as `x - max - ln(sum(exp(x - max)))` also avoids taking the logarithm of
+    /// values which underflowed to zero.
+    LogSoftmax(Input, usize),
+    /// Expands the input to the given shape, following the same rules as the implicit broadcasting
+    /// of the elementwise ops.
+    ///
+    /// Every element of the input is used for several elements of the output, so the backward pass
+    /// sums the delta along the broadcasted axes.
+    Broadcast(Input, Vec<usize>),
+    /// Joins the inputs together along an axis, in order.
+    ///
+    /// Every input must have the same shape except along the axis. Like [Op::Stack], it takes a
+    /// variable number of inputs.
+    Concat(Vec<Input>, usize),
+    /// Stacks the inputs along a new axis inserted at the given index, in order.
+    ///
+    /// Unlike [Op::Concat], every input must have the same shape, and the output has one more
+    /// axis whose length is the number of inputs.
+    Stack(Vec<Input>, usize),
+    /// Extracts a subtensor, keeping the elements from `start` (inclusive) to `end` (exclusive) of
+    /// each `(start, end)` range along the axis at the same position.
+    ///
+    /// The backward pass scatters the delta back into a zero tensor of the input's shape, since the
+    /// elements outside of the ranges don't affect the output.
+    Slice(Input, Vec<(usize, usize)>),
+    /// Randomly zeroes elements of the input with probability `p` while training, scaling the
+    /// kept ones by `1 / (1 - p)` so the expected value of every element doesn't change.
+    ///
+    /// Outside of training (see [Backend::forward_train]) it is the identity. The backward pass
+    /// uses the same mask as the forward pass, so the delta of dropped elements is zero.
+    Dropout(Input, f64),
+    /// Reciprocal of the input, `1 / x`.
+    ///
+    /// Backends must follow IEEE 754 at zero, so `1 / 0` is infinity with the sign of the zero and
+    /// its delta is `-1 / x^2`, negative infinity.
+    Reciprocal(Input),
+    /// Sine of the input, in radians.
+    ///
+    /// The backward pass multiplies the delta by the derivative `cos(x)`.
+    Sin(Input),
+    /// Cosine of the input, in radians.
+    ///
+    /// The backward pass multiplies the delta by the derivative `-sin(x)`.
+    Cos(Input),
+    /// Pads the input with `value`, adding `before` elements at the start and `after` elements
+    /// at the end of the axis at the same position as each `(before, after)` pair.
+    ///
+    /// Only the original region of the output depends on the input, so the backward pass slices
+    /// it out of the delta.
+    Pad(Input, Vec<(usize, usize)>, f64),
+    /// 2D convolution (strictly, cross-correlation) of the input by the kernel.
+    ///
+    /// Both use the NCHW layout: the input is `(batch, channels, height, width)` and the kernel
+    /// is `(out_channels, channels, kernel_height, kernel_width)`. The output is
+    /// `(batch, out_channels, out_height, out_width)`, where each output size is
+    /// `(size + 2 * padding - kernel_size) / stride + 1` with the `(height, width)` values of the
+    /// stride and padding. Padding adds zeros on both sides of each spatial axis.
+    Conv2d {
+        input: Input,
+        kernel: Input,
+        stride: (usize, usize),
+        padding: (usize, usize),
+    },
+    /// 2D max pooling of an input in the NCHW layout, see [Op::Conv2d]: every output element is
+    /// the maximum of a `kernel` sized window of the input, with windows `stride` apart.
+    ///
+    /// Each output size is `(size - kernel_size) / stride + 1`. Only the maximum element of a
+    /// window affects the output, so the backward pass routes the delta of every window to its
+    /// maximum element only (the first one if there is a tie) and the rest get zero.
+    MaxPool2d {
+        input: Input,
+        kernel: (usize, usize),
+        stride: (usize, usize),
+    },
+    /// Looks up rows of the `table`, usually a trainable parameter of shape
+    /// `(vocabulary, features)`, selected by the `indices`.
+    ///
+    /// The indices are whole numbers stored in the elements of the tensor. The output has the
+    /// shape of the indices followed by `features`. The backward pass scatters the delta into
+    /// the selected rows of the table only, summing it for rows selected several times, and the
+    /// indices get a zero delta.
+    Embedding {
+        table: Input,
+        indices: Input,
+    },
+    /// Batch normalization: normalizes every feature of the input over the batch, its first
+    /// axis, to zero mean and unit variance, then scales it by `gamma` and shifts it by `beta`.
+    ///
+    /// `gamma` and `beta` have the shape of the input without its first axis. While training
+    /// (see [Backend::forward_train]) the statistics of the batch are used, and backends update
+    /// running statistics kept in their state with [Backend::update_state]. Otherwise the
+    /// running statistics are used.
+    ///
+    /// Output `0` holds the result, outputs `1` and `2` hold the mean and variance which were
+    /// used.
+    BatchNorm {
+        input: Input,
+        gamma: Input,
+        beta: Input,
+    },
+    /// Layer normalization: normalizes the input over its last axis to zero mean and unit
+    /// variance, then scales it by `gamma` and shifts it by `beta`, which have the size of the
+    /// last axis.
+    ///
+    /// `eps` is added to the variance before taking its square root, as in
+    /// `(x - mean) / sqrt(variance + eps)`, so it also appears in the backward pass, which is
+    /// left to the backends.
+    LayerNorm {
+        input: Input,
+        gamma: Input,
+        beta: Input,
+        eps: f64,
+    },
+    /// Gaussian error linear unit, `x * Φ(x)` where `Φ` is the cumulative distribution function
+    /// of the standard normal distribution.
+    ///
+    /// Backends must compute the exact form `0.5 * x * (1 + erf(x / sqrt(2)))` rather than the
+    /// tanh approximation, so results agree across backends.
+    Gelu(Input),
+    /// Like [Op::Relu], but negative elements are multiplied by the slope instead of being
+    /// zeroed.
+    ///
+    /// The backward pass multiplies the delta of negative elements by the slope as well.
+    LeakyRelu(Input, f64),
+    /// The elementwise square of the difference of the inputs, like an [Op::Square] of an
+    /// [Op::Sub], in a single op. [Graph::fuse] produces it.
+    SquaredDiff(Input, Input),
     TrainConst(Vec<usize>, f64),
+    /// A trainable tensor of the given shape whose elements are initialized according to the
+    /// [InitKind], using the rng passed to [Backend::state].
+    TrainInit(Vec<usize>, InitKind),
+}
+
+/// How the elements of an [Op::TrainInit] are initialized.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InitKind {
+    /// Every element is set to the value, like [Op::TrainConst].
+    Const(f64),
+    /// Elements are sampled uniformly from `low` (inclusive) to `high` (exclusive).
+    Uniform { low: f64, high: f64 },
+    /// Elements are sampled from a normal distribution.
+    Normal { mean: f64, std: f64 },
+    /// Glorot/Xavier uniform initialization: elements are sampled uniformly from
+    /// `-limit` to `limit` where `limit = sqrt(6 / (fan_in + fan_out))`.
+    ///
+    /// The fan in is the size of the first axis and the fan out is the product of the sizes of
+    /// the rest of them, which matches the `(k, m)` weights of an [Op::MatMul].
+    Xavier,
 }
 
 impl Op {
@@ -42,21 +304,463 @@ impl Op {
                 a.shift_inputs(shift);
                 b.shift_inputs(shift);
             }
+            Self::Mul(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
+            Self::Div(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
             Self::Square(a) => {
                 a.shift_inputs(shift);
             }
+            Self::Neg(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Exp(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Log(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Sqrt(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Tanh(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Sigmoid(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Relu(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::ReluThreshold(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Pow(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Max(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
+            Self::Min(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
+            Self::Sum(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Mean(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::SumAxis(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Reshape(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Transpose(a, _, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::MatMul(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
+            Self::Const(..) => {}
+            Self::SplitAt(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Clamp(a, _, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Abs(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Softmax(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::LogSoftmax(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Broadcast(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Concat(inputs, _) | Self::Stack(inputs, _) => {
+                for a in inputs {
+                    a.shift_inputs(shift);
+                }
+            }
+            Self::Slice(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Dropout(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Reciprocal(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Sin(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Cos(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::Pad(a, _, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::Conv2d { input, kernel, .. } => {
+                input.shift_inputs(shift);
+                kernel.shift_inputs(shift);
+            }
+            Self::MaxPool2d { input, .. } => {
+                input.shift_inputs(shift);
+            }
+            Self::Embedding { table, indices } => {
+                table.shift_inputs(shift);
+                indices.shift_inputs(shift);
+            }
+            Self::BatchNorm { input, gamma, beta } => {
+                input.shift_inputs(shift);
+                gamma.shift_inputs(shift);
+                beta.shift_inputs(shift);
+            }
+            Self::LayerNorm {
+                input, gamma, beta, ..
+            } => {
+                input.shift_inputs(shift);
+                gamma.shift_inputs(shift);
+                beta.shift_inputs(shift);
+            }
+            Self::Gelu(a) => {
+                a.shift_inputs(shift);
+            }
+            Self::LeakyRelu(a, _) => {
+                a.shift_inputs(shift);
+            }
+            Self::SquaredDiff(a, b) => {
+                a.shift_inputs(shift);
+                b.shift_inputs(shift);
+            }
             Self::TrainConst(..) => {}
+            Self::TrainInit(..) => {}
+        }
+    }
+
+    /// The number of [Input]s the op consumes.
+    pub fn input_count(&self) -> usize {
+        self.inputs().len()
+    }
+
+    /// The number of outputs the op produces.
+    fn output_count(&self) -> usize {
+        match self {
+            Self::SplitAt(..) => 2,
+            Self::BatchNorm { .. } => 3,
+            _ => 1,
+        }
+    }
+
+    /// All the [Input]s the op consumes, in the order they appear in the variant.
+    pub fn inputs(&self) -> Vec<&Input> {
+        match self {
+            Self::Add(a, b)
+            | Self::Sub(a, b)
+            | Self::Mul(a, b)
+            | Self::Div(a, b)
+            | Self::Max(a, b)
+            | Self::Min(a, b)
+            | Self::MatMul(a, b)
+            | Self::Conv2d {
+                input: a,
+                kernel: b,
+                ..
+            }
+            | Self::Embedding {
+                table: a,
+                indices: b,
+            }
+            | Self::SquaredDiff(a, b) => vec![a, b],
+            Self::Square(a)
+            | Self::Neg(a)
+            | Self::Exp(a)
+            | Self::Log(a)
+            | Self::Sqrt(a)
+            | Self::Tanh(a)
+            | Self::Sigmoid(a)
+            | Self::Relu(a)
+            | Self::ReluThreshold(a, _)
+            | Self::Pow(a, _)
+            | Self::Sum(a)
+            | Self::Mean(a)
+            | Self::SumAxis(a, _)
+            | Self::Reshape(a, _)
+            | Self::Transpose(a, _, _)
+            | Self::SplitAt(a, _)
+            | Self::Clamp(a, _, _)
+            | Self::Abs(a)
+            | Self::Softmax(a, _)
+            | Self::LogSoftmax(a, _)
+            | Self::Broadcast(a, _)
+            | Self::Slice(a, _)
+            | Self::Dropout(a, _)
+            | Self::Reciprocal(a)
+            | Self::Sin(a)
+            | Self::Cos(a)
+            | Self::Pad(a, _, _)
+            | Self::MaxPool2d { input: a, .. }
+            | Self::Gelu(a)
+            | Self::LeakyRelu(a, _) => vec![a],
+            Self::BatchNorm { input, gamma, beta }
+            | Self::LayerNorm {
+                input, gamma, beta, ..
+            } => vec![input, gamma, beta],
+            Self::Concat(inputs, _) | Self::Stack(inputs, _) => inputs.iter().collect(),
+            Self::Const(..) | Self::TrainConst(..) | Self::TrainInit(..) => vec![],
+        }
+    }
+
+    fn inputs_mut(&mut self) -> Vec<&mut Input> {
+        match self {
+            Self::Add(a, b)
+            | Self::Sub(a, b)
+            | Self::Mul(a, b)
+            | Self::Div(a, b)
+            | Self::Max(a, b)
+            | Self::Min(a, b)
+            | Self::MatMul(a, b)
+            | Self::Conv2d {
+                input: a,
+                kernel: b,
+                ..
+            }
+            | Self::Embedding {
+                table: a,
+                indices: b,
+            }
+            | Self::SquaredDiff(a, b) => vec![a, b],
+            Self::Square(a)
+            | Self::Neg(a)
+            | Self::Exp(a)
+            | Self::Log(a)
+            | Self::Sqrt(a)
+            | Self::Tanh(a)
+            | Self::Sigmoid(a)
+            | Self::Relu(a)
+            | Self::ReluThreshold(a, _)
+            | Self::Pow(a, _)
+            | Self::Sum(a)
+            | Self::Mean(a)
+            | Self::SumAxis(a, _)
+            | Self::Reshape(a, _)
+            | Self::Transpose(a, _, _)
+            | Self::SplitAt(a, _)
+            | Self::Clamp(a, _, _)
+            | Self::Abs(a)
+            | Self::Softmax(a, _)
+            | Self::LogSoftmax(a, _)
+            | Self::Broadcast(a, _)
+            | Self::Slice(a, _)
+            | Self::Dropout(a, _)
+            | Self::Reciprocal(a)
+            | Self::Sin(a)
+            | Self::Cos(a)
+            | Self::Pad(a, _, _)
+            | Self::MaxPool2d { input: a, .. }
+            | Self::Gelu(a)
+            | Self::LeakyRelu(a, _) => vec![a],
+            Self::BatchNorm { input, gamma, beta }
+            | Self::LayerNorm {
+                input, gamma, beta, ..
+            } => vec![input, gamma, beta],
+            Self::Concat(inputs, _) | Self::Stack(inputs, _) => inputs.iter_mut().collect(),
+            Self::Const(..) | Self::TrainConst(..) | Self::TrainInit(..) => vec![],
+        }
+    }
+}
+
+impl Op {
+    /// Feeds the variant, inputs and parameters of the op to `state`.
+    ///
+    /// This isn't a [Hash] impl because floats are hashed by their bits, which disagrees with
+    /// [PartialEq] for `0.0` and `-0.0`.
+    fn fingerprint(&self, state: &mut Fnv1a) {
+        state.usize(OpTy::from(self) as usize);
+        let inputs = self.inputs();
+        state.usize(inputs.len());
+        for input in inputs {
+            match input {
+                Input::Feed(name) => {
+                    state.usize(0);
+                    state.usize(name.len());
+                    state.write(name.as_bytes());
+                }
+                Input::Internal(Internal { node, output }) => {
+                    state.usize(1);
+                    state.usize(*node);
+                    state.usize(*output);
+                }
+            }
+        }
+        match self {
+            Self::ReluThreshold(_, value)
+            | Self::Pow(_, value)
+            | Self::Dropout(_, value)
+            | Self::LeakyRelu(_, value)
+            | Self::LayerNorm { eps: value, .. } => state.f64(*value),
+            Self::SumAxis(_, index)
+            | Self::SplitAt(_, index)
+            | Self::Softmax(_, index)
+            | Self::LogSoftmax(_, index)
+            | Self::Concat(_, index)
+            | Self::Stack(_, index) => state.usize(*index),
+            Self::Reshape(_, shape) | Self::Broadcast(_, shape) => state.usizes(shape),
+            Self::Slice(_, ranges) => state.pairs(ranges),
+            Self::Transpose(_, axis_a, axis_b) => state.pairs(&[(*axis_a, *axis_b)]),
+            Self::Clamp(_, min, max) => {
+                state.f64(*min);
+                state.f64(*max);
+            }
+            Self::Pad(_, padding, value) => {
+                state.pairs(padding);
+                state.f64(*value);
+            }
+            Self::Conv2d {
+                stride, padding, ..
+            } => state.pairs(&[*stride, *padding]),
+            Self::MaxPool2d { kernel, stride, .. } => state.pairs(&[*kernel, *stride]),
+            Self::Const(shape, value) | Self::TrainConst(shape, value) => {
+                state.usizes(shape);
+                state.f64(*value);
+            }
+            Self::TrainInit(shape, kind) => {
+                state.usizes(shape);
+                match kind {
+                    InitKind::Const(value) => {
+                        state.usize(0);
+                        state.f64(*value);
+                    }
+                    InitKind::Uniform { low, high } => {
+                        state.usize(1);
+                        state.f64(*low);
+                        state.f64(*high);
+                    }
+                    InitKind::Normal { mean, std } => {
+                        state.usize(2);
+                        state.f64(*mean);
+                        state.f64(*std);
+                    }
+                    InitKind::Xavier => state.usize(3),
+                }
+            }
+            Self::Add(..)
+            | Self::Sub(..)
+            | Self::Mul(..)
+            | Self::Div(..)
+            | Self::Max(..)
+            | Self::Min(..)
+            | Self::MatMul(..)
+            | Self::Square(..)
+            | Self::Neg(..)
+            | Self::Exp(..)
+            | Self::Log(..)
+            | Self::Sqrt(..)
+            | Self::Tanh(..)
+            | Self::Sigmoid(..)
+            | Self::Relu(..)
+            | Self::Sum(..)
+            | Self::Mean(..)
+            | Self::Abs(..)
+            | Self::Reciprocal(..)
+            | Self::Sin(..)
+            | Self::Cos(..)
+            | Self::Embedding { .. }
+            | Self::BatchNorm { .. }
+            | Self::Gelu(..)
+            | Self::SquaredDiff(..) => {}
         }
     }
 }
 
+/// Renders the op as its name followed by its inputs and then its parameters, like `Add(#0, x)`
+/// or `Pow(#2, 3)`.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params = match self {
+            Self::ReluThreshold(_, threshold) => vec![threshold.to_string()],
+            Self::Pow(_, exponent) => vec![exponent.to_string()],
+            Self::Dropout(_, p) => vec![p.to_string()],
+            Self::LeakyRelu(_, slope) => vec![slope.to_string()],
+            Self::LayerNorm { eps, .. } => vec![eps.to_string()],
+            Self::SumAxis(_, axis)
+            | Self::Softmax(_, axis)
+            | Self::LogSoftmax(_, axis)
+            | Self::Concat(_, axis)
+            | Self::Stack(_, axis) => {
+                vec![axis.to_string()]
+            }
+            Self::Reshape(_, shape) | Self::Broadcast(_, shape) => vec![format!("{:?}", shape)],
+            Self::Slice(_, ranges) => vec![format!("{:?}", ranges)],
+            Self::Transpose(_, axis_a, axis_b) => vec![axis_a.to_string(), axis_b.to_string()],
+            Self::SplitAt(_, index) => vec![index.to_string()],
+            Self::Clamp(_, min, max) => vec![min.to_string(), max.to_string()],
+            Self::Pad(_, padding, value) => vec![format!("{:?}", padding), value.to_string()],
+            Self::Conv2d {
+                stride, padding, ..
+            } => vec![format!("{:?}", stride), format!("{:?}", padding)],
+            Self::MaxPool2d { kernel, stride, .. } => {
+                vec![format!("{:?}", kernel), format!("{:?}", stride)]
+            }
+            Self::Const(shape, value) | Self::TrainConst(shape, value) => {
+                vec![format!("{:?}", shape), value.to_string()]
+            }
+            Self::TrainInit(shape, kind) => vec![format!("{:?}", shape), format!("{:?}", kind)],
+            Self::Add(..)
+            | Self::Sub(..)
+            | Self::Mul(..)
+            | Self::Div(..)
+            | Self::Max(..)
+            | Self::Min(..)
+            | Self::MatMul(..)
+            | Self::Square(..)
+            | Self::Neg(..)
+            | Self::Exp(..)
+            | Self::Log(..)
+            | Self::Sqrt(..)
+            | Self::Tanh(..)
+            | Self::Sigmoid(..)
+            | Self::Relu(..)
+            | Self::Sum(..)
+            | Self::Mean(..)
+            | Self::Abs(..)
+            | Self::Reciprocal(..)
+            | Self::Sin(..)
+            | Self::Cos(..)
+            | Self::Embedding { .. }
+            | Self::BatchNorm { .. }
+            | Self::Gelu(..)
+            | Self::SquaredDiff(..) => vec![],
+        };
+        let args: Vec<String> = self
+            .inputs()
+            .into_iter()
+            .map(|input| input.to_string())
+            .chain(params)
+            .collect();
+        write!(f, "{:?}({})", OpTy::from(self), args.join(", "))
+    }
+}
+
 /// Inputs which are used by an operation [Op].
 ///
 /// They can either be a:
 /// * String which will be used to fetch the actual Tensor from a dictionary later
-/// (a HashMap<String, Tensor> for example)
+///   (a HashMap<String, Tensor> for example)
 /// * Internal which holds the index of the node in the [Graph] from where to get the input from
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Input {
     /// A String corresponding to the Key to use when fetching the actual Tensor from the feed dict.
     /// For example, if we had a HashMap<String, Tensor>
@@ -66,23 +770,118 @@ pub enum Input {
 }
 
 impl Input {
+    /// The first output of the op at `node`.
+    pub fn internal(node: usize) -> Self {
+        Self::internal_out(node, 0)
+    }
+
+    /// The output `output` of the op at `node`.
+    pub fn internal_out(node: usize, output: usize) -> Self {
+        Self::Internal(Internal { node, output })
+    }
+
     fn shift_inputs(&mut self, shift: usize) {
         if let Self::Internal(n) = self {
             n.shift_inputs(shift);
         }
     }
+
+    /// Rewrites an internal input with [Internal::remap], feeds are left as they are.
+    pub fn remap(&mut self, map: &HashMap<usize, usize>) {
+        if let Self::Internal(n) = self {
+            n.remap(map);
+        }
+    }
+}
+
+/// Renders feeds by their name and internal inputs as `#node`, or `#node.output` when the output
+/// isn't `0`.
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Feed(name) => write!(f, "{}", name),
+            Self::Internal(Internal { node, output: 0 }) => write!(f, "#{}", node),
+            Self::Internal(Internal { node, output }) => write!(f, "#{}.{}", node, output),
+        }
+    }
+}
+
+impl From<String> for Input {
+    fn from(s: String) -> Input {
+        Input::Feed(s)
+    }
 }
 
 impl From<&str> for Input {
     fn from(s: &str) -> Input {
-        Input::Feed(s.to_owned())
+        s.to_owned().into()
+    }
+}
+
+/// An error of the operations shared by every backend, such as analysing a [Graph].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeepError {
+    /// The op at `node` depends on `input`, which doesn't come before it, so the graph can't be
+    /// evaluated in order.
+    Cycle { node: usize, input: Internal },
+    /// An input refers to this node, which is not in the [Graph].
+    DanglingNode(usize),
+    /// An input refers to an output which the op at its node doesn't have.
+    DanglingOutput(Internal),
+    /// The shapes of the inputs of the op at `node` can't be used with it, either together or
+    /// with its parameters (like an axis the input doesn't have).
+    ShapeMismatch {
+        node: usize,
+        shapes: Vec<Vec<usize>>,
+    },
+    /// An op uses this feed, which wasn't provided.
+    UnknownFeed(String),
+    /// The backend doesn't implement this [Backend] method, such as [Backend::save_state] for
+    /// a backend without serialization.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for DeepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle { node, input } => write!(
+                f,
+                "node {} depends on node {} which does not come before it",
+                node, input.node
+            ),
+            Self::DanglingNode(node) => write!(f, "node {} doesn't exist", node),
+            Self::DanglingOutput(internal) => write!(
+                f,
+                "output {} of node {} doesn't exist",
+                internal.output, internal.node
+            ),
+            Self::ShapeMismatch { node, shapes } => {
+                write!(f, "node {} got incompatible shapes {:?}", node, shapes)
+            }
+            Self::UnknownFeed(name) => write!(f, "feed \"{}\" wasn't provided", name),
+            Self::Unsupported(method) => write!(f, "the backend doesn't support {}", method),
+        }
     }
 }
 
-#[derive(Clone, Default, Debug)]
+impl std::error::Error for DeepError {}
+
+/// The ops computing tensors, each referring to the outputs of earlier ones.
+///
+/// Graphs are equal when their ops are equal one by one, with the same variants, inputs and
+/// parameters, and the same nodes are frozen. Parameters are compared as floats, so `NaN` never
+/// equals itself and `0.0` equals `-0.0`, unlike with [Graph::fingerprint].
+#[derive(Clone, Default, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Graph {
     /// A series of [Op]s referring to each other's outputs for their input.
-    pub ops: Vec<Op>,
+    ///
+    /// Private so that every op is added with [Graph::append], which checks that its inputs
+    /// refer to ops already in the graph.
+    ops: Vec<Op>,
+    /// The trainable nodes which must not be trained, see [Graph::freeze].
+    #[cfg_attr(feature = "serde", serde(default))]
+    frozen: BTreeSet<usize>,
 }
 
 impl Graph {
@@ -93,6 +892,8 @@ impl Graph {
     pub fn merge(&mut self, other: Graph) {
         let current = self.ops.len();
         self.ops.extend(other.ops);
+        self.frozen
+            .extend(other.frozen.into_iter().map(|node| node + current));
         for op in &mut self.ops[current..] {
             op.shift_inputs(current);
         }
@@ -105,11 +906,496 @@ impl Graph {
         input
     }
 
+    /// The number of nodes ([Op]s) in the graph.
+    pub fn node_count(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// The ops of the graph, indexed by their node.
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
     /// Returns the node index of the appended [Op].
-    pub fn append(&mut self, op: Op) -> usize {
+    ///
+    /// Fails with [DeepError::DanglingNode] or [DeepError::DanglingOutput] if one of the
+    /// [Internal] inputs of the op doesn't refer to an output of an op already in the graph, in
+    /// which case the graph is unchanged.
+    pub fn append(&mut self, op: Op) -> Result<usize, DeepError> {
+        self.check_inputs(&op)?;
+        Ok(self.append_unchecked(op))
+    }
+
+    /// Like [Graph::append], but without checking the inputs of the op.
+    ///
+    /// The graph may then refer to nodes which don't exist or which come later, which
+    /// [Graph::validate] and [Graph::topological_order] report.
+    pub fn append_unchecked(&mut self, op: Op) -> usize {
         self.ops.push(op);
         self.ops.len() - 1
     }
+
+    /// Checks that every [Internal] input refers to an existing node and output.
+    pub fn validate(&self) -> Result<(), DeepError> {
+        let mut validator = visitor::Validator {
+            graph: self,
+            result: Ok(()),
+        };
+        self.accept(&mut validator);
+        validator.result
+    }
+
+    /// Checks that every [Internal] input of `op` refers to an existing node and output.
+    fn check_inputs(&self, op: &Op) -> Result<(), DeepError> {
+        for input in op.inputs() {
+            if let Input::Internal(internal) = *input {
+                match self.ops.get(internal.node) {
+                    None => return Err(DeepError::DanglingNode(internal.node)),
+                    Some(source) if internal.output >= source.output_count() => {
+                        return Err(DeepError::DanglingOutput(internal));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the names of all the [Input::Feed]s the graph needs, which are the keys the
+    /// [Backend::TensorDict] must provide.
+    pub fn feed_keys(&self) -> BTreeSet<String> {
+        let mut feeds = visitor::FeedKeys::default();
+        self.accept(&mut feeds);
+        feeds.0
+    }
+
+    /// Calls [GraphVisitor::visit_op] for every op, in order.
+    pub fn accept(&self, visitor: &mut impl GraphVisitor) {
+        for (index, op) in self.ops.iter().enumerate() {
+            visitor.visit_op(index, op);
+        }
+    }
+
+    /// Calls `f` on every op, in order, with its node index, so that passes can rewrite ops in
+    /// place.
+    ///
+    /// Nodes keep their indices, but the rewritten inputs aren't checked, see
+    /// [Graph::validate].
+    pub fn map_ops(&mut self, mut f: impl FnMut(usize, &mut Op)) {
+        for (index, op) in self.ops.iter_mut().enumerate() {
+            f(index, op);
+        }
+    }
+
+    /// Renames the feed `old` to `new` in every op which uses it.
+    pub fn replace_feed(&mut self, old: &str, new: &str) {
+        for input in self.ops.iter_mut().flat_map(Op::inputs_mut) {
+            if let Input::Feed(name) = input {
+                if name == old {
+                    *name = new.to_owned();
+                }
+            }
+        }
+    }
+
+    /// Splices `replacement` in where the feed `key` was: every op using the feed uses
+    /// `replacement_output` of the `replacement` graph instead.
+    ///
+    /// The `replacement` ops are placed before the current ones so that ops keep coming after
+    /// their dependencies, which shifts the current ops. Returns a map from their old node
+    /// indices to the new ones.
+    pub fn substitute_feed(
+        &mut self,
+        key: &str,
+        mut replacement: Graph,
+        replacement_output: Input,
+    ) -> HashMap<usize, usize> {
+        let shift = replacement.ops.len();
+        let remap = (0..self.ops.len())
+            .map(|node| (node, node + shift))
+            .collect();
+        replacement.merge(std::mem::take(self));
+        for input in replacement.ops[shift..].iter_mut().flat_map(Op::inputs_mut) {
+            if matches!(input, Input::Feed(name) if name == key) {
+                *input = replacement_output.clone();
+            }
+        }
+        *self = replacement;
+        remap
+    }
+
+    /// Renders the graph in the Graphviz DOT format.
+    ///
+    /// Every op is a node labeled with its [OpTy] and every feed is a box shaped node labeled
+    /// with its name. Edges go from an input to the op consuming it and are labeled with the
+    /// output index if it isn't `0`.
+    pub fn to_dot(&self) -> String {
+        use fmt::Write;
+
+        let mut dot = String::from("digraph {\n");
+        // Writing into a `String` never fails, so the results are ignored.
+        for name in self.feed_keys() {
+            let name = visitor::Dot::escape(&name);
+            let _ = writeln!(
+                dot,
+                "    \"feed_{}\" [label=\"{}\", shape=box];",
+                name, name
+            );
+        }
+        let mut body = visitor::Dot::default();
+        self.accept(&mut body);
+        dot.push_str(&body.nodes);
+        dot.push_str(&body.edges);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Hashes the structure of the graph: the variant, inputs and parameters of every op.
+    ///
+    /// Structurally identical graphs always have the same fingerprint. It uses a fixed hash
+    /// function rather than [std]'s default one, whose algorithm is unspecified, and hashes
+    /// sizes and indices as little-endian `u64` so it doesn't depend on the platform.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        for op in &self.ops {
+            op.fingerprint(&mut hasher);
+        }
+        hasher.usize(self.frozen.len());
+        for &node in &self.frozen {
+            hasher.usize(node);
+        }
+        hasher.finish()
+    }
+
+    /// Lists the ops one per line as `%node = Op`, using the [Display](fmt::Display) impl of
+    /// [Op], so the graph reads like SSA form.
+    pub fn pretty(&self) -> String {
+        self.ops
+            .iter()
+            .enumerate()
+            .map(|(node, op)| format!("%{} = {}\n", node, op))
+            .collect()
+    }
+
+    /// Removes duplicated ops: an op which is identical to an earlier op (same variant, same
+    /// inputs and same parameters) is dropped, and anything using it uses the earlier op instead.
+    ///
+    /// [Op::TrainConst]s and [Op::TrainInit]s are never merged, even with the same shape and
+    /// initialization, as they are independent trainable parameters. Neither are
+    /// [Op::Dropout]s, which draw independent masks, nor [Op::BatchNorm]s, which keep their own
+    /// running statistics.
+    ///
+    /// Returns a map from the old node indices to the new ones.
+    pub fn dedup(&mut self) -> HashMap<usize, usize> {
+        let mut remap = HashMap::new();
+        let mut ops: Vec<Op> = Vec::with_capacity(self.ops.len());
+        for (node, mut op) in self.ops.drain(..).enumerate() {
+            for input in op.inputs_mut() {
+                input.remap(&remap);
+            }
+            let existing = match op {
+                Op::TrainConst(..) | Op::TrainInit(..) | Op::Dropout(..) | Op::BatchNorm { .. } => {
+                    None
+                }
+                _ => ops.iter().position(|other| *other == op),
+            };
+            let new_node = existing.unwrap_or_else(|| {
+                ops.push(op);
+                ops.len() - 1
+            });
+            remap.insert(node, new_node);
+        }
+        self.ops = ops;
+        self.remap_frozen(&remap);
+        remap
+    }
+
+    /// Replaces the [Op::Add], [Op::Sub], [Op::Square], [Op::Neg] and [Op::Reciprocal] ops whose
+    /// inputs are all scalar [Op::Const]s with a single [Op::Const] holding the result.
+    ///
+    /// [Op::TrainConst]s are never folded since they change during training. The constants which
+    /// were only used by folded ops are removed, which shifts the ops after them. Returns a map
+    /// from the old node indices of the kept ops to their new ones, which can be used to fix up
+    /// any other [Input] referring to this graph.
+    pub fn fold_constants(&mut self) -> HashMap<usize, usize> {
+        let mut uses = vec![0usize; self.ops.len()];
+        for input in self.ops.iter().flat_map(Op::inputs) {
+            if let Input::Internal(internal) = input {
+                uses[internal.node] += 1;
+            }
+        }
+
+        let mut removed = vec![false; self.ops.len()];
+        for node in 0..self.ops.len() {
+            let scalar = |input: &Input| match input {
+                Input::Internal(internal) => match &self.ops[internal.node] {
+                    Op::Const(shape, value) if shape.is_empty() => Some(*value),
+                    _ => None,
+                },
+                Input::Feed(_) => None,
+            };
+            let binary =
+                |a, b, f: fn(f64, f64) -> f64| scalar(a).zip(scalar(b)).map(|(a, b)| f(a, b));
+            let folded = match &self.ops[node] {
+                Op::Add(a, b) => binary(a, b, |a, b| a + b),
+                Op::Sub(a, b) => binary(a, b, |a, b| a - b),
+                Op::Square(a) => scalar(a).map(|a| a * a),
+                Op::Neg(a) => scalar(a).map(|a| -a),
+                Op::Reciprocal(a) => scalar(a).map(|a| 1.0 / a),
+                _ => None,
+            };
+            if let Some(value) = folded {
+                for input in self.ops[node].inputs() {
+                    if let Input::Internal(internal) = input {
+                        uses[internal.node] -= 1;
+                        removed[internal.node] = uses[internal.node] == 0;
+                    }
+                }
+                self.ops[node] = Op::Const(vec![], value);
+            }
+        }
+
+        let mut remap = HashMap::new();
+        let ops = std::mem::take(&mut self.ops);
+        for (node, mut op) in ops.into_iter().enumerate() {
+            if removed[node] {
+                continue;
+            }
+            for input in op.inputs_mut() {
+                input.remap(&remap);
+            }
+            remap.insert(node, self.append_unchecked(op));
+        }
+        self.remap_frozen(&remap);
+        remap
+    }
+
+    /// Fuses every [Op::Square] of an [Op::Sub] into a single [Op::SquaredDiff], when the
+    /// [Op::Sub] isn't used by any other op.
+    ///
+    /// The fused [Op::Sub]s are removed, which shifts the ops after them. Returns a map from the
+    /// old node indices of the kept ops to their new ones, where a fused [Op::Square] maps to its
+    /// [Op::SquaredDiff].
+    pub fn fuse(&mut self) -> HashMap<usize, usize> {
+        let mut uses = vec![0usize; self.ops.len()];
+        for input in self.ops.iter().flat_map(Op::inputs) {
+            if let Input::Internal(internal) = input {
+                uses[internal.node] += 1;
+            }
+        }
+
+        let mut fused = vec![false; self.ops.len()];
+        for node in 0..self.ops.len() {
+            if let Op::Square(Input::Internal(internal)) = &self.ops[node] {
+                if let (Op::Sub(a, b), 1) = (&self.ops[internal.node], uses[internal.node]) {
+                    fused[internal.node] = true;
+                    self.ops[node] = Op::SquaredDiff(a.clone(), b.clone());
+                }
+            }
+        }
+
+        let mut remap = HashMap::new();
+        let ops = std::mem::take(&mut self.ops);
+        for (node, mut op) in ops.into_iter().enumerate() {
+            if fused[node] {
+                continue;
+            }
+            for input in op.inputs_mut() {
+                input.remap(&remap);
+            }
+            remap.insert(node, self.append_unchecked(op));
+        }
+        self.remap_frozen(&remap);
+        remap
+    }
+
+    /// Removes every op which `output` doesn't depend on.
+    ///
+    /// Returns a map from the old node indices of the kept ops to their new ones, which can be used
+    /// to fix up any other [Input] referring to this graph.
+    pub fn prune(&mut self, output: &Input) -> HashMap<usize, usize> {
+        let mut reachable = vec![false; self.ops.len()];
+        if let Input::Internal(internal) = output {
+            reachable[internal.node] = true;
+        }
+        // Ops come after their dependencies, so walking backwards visits every user of a node
+        // before the node itself.
+        for node in (0..self.ops.len()).rev() {
+            if reachable[node] {
+                for input in self.ops[node].inputs() {
+                    if let Input::Internal(internal) = input {
+                        reachable[internal.node] = true;
+                    }
+                }
+            }
+        }
+
+        let mut remap = HashMap::new();
+        let ops = std::mem::take(&mut self.ops);
+        for (node, mut op) in ops.into_iter().enumerate() {
+            if !reachable[node] {
+                continue;
+            }
+            for input in op.inputs_mut() {
+                input.remap(&remap);
+            }
+            remap.insert(node, self.append_unchecked(op));
+        }
+        self.remap_frozen(&remap);
+        remap
+    }
+
+    /// Rewrites the frozen nodes through a map from old to new node indices, dropping the ones
+    /// which were removed.
+    fn remap_frozen(&mut self, map: &HashMap<usize, usize>) {
+        self.frozen = self
+            .frozen
+            .iter()
+            .filter_map(|node| map.get(node).copied())
+            .collect();
+    }
+
+    /// Freezes the trainable op at `node`, so backends don't produce a delta for it and training
+    /// leaves its state unchanged, while it keeps its current value.
+    ///
+    /// Freezing is kept when the graph is merged or its nodes are remapped.
+    pub fn freeze(&mut self, node: usize) {
+        self.frozen.insert(node);
+    }
+
+    /// Makes a node frozen with [Graph::freeze] trainable again.
+    pub fn unfreeze(&mut self, node: usize) {
+        self.frozen.remove(&node);
+    }
+
+    /// Whether the node was frozen with [Graph::freeze].
+    pub fn is_frozen(&self, node: usize) -> bool {
+        self.frozen.contains(&node)
+    }
+
+    /// Returns the node indices in an order where every node comes after all the nodes it
+    /// depends on, which is an order the graph can be evaluated in.
+    ///
+    /// Ops are always appended after their dependencies, so this is the order of the ops.
+    /// Fails with [DeepError::Cycle] if a node depends on itself or on a node which comes after
+    /// it.
+    pub fn topological_order(&self) -> Result<Vec<usize>, DeepError> {
+        for (node, op) in self.ops.iter().enumerate() {
+            for input in op.inputs() {
+                if let Input::Internal(internal) = *input {
+                    if internal.node >= node {
+                        return Err(DeepError::Cycle {
+                            node,
+                            input: internal,
+                        });
+                    }
+                }
+            }
+        }
+        Ok((0..self.ops.len()).collect())
+    }
+
+    /// Whether no node depends on itself, directly or through other nodes.
+    ///
+    /// Unlike [Graph::topological_order], a node may depend on a node which comes after it, as
+    /// long as that doesn't close a cycle. Inputs referring to nodes which don't exist are
+    /// ignored, [Graph::validate] reports those.
+    pub fn is_acyclic(&self) -> bool {
+        #[derive(Copy, Clone, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+
+        let dependencies = |node: usize| {
+            self.ops[node]
+                .inputs()
+                .into_iter()
+                .filter_map(|input| match input {
+                    Input::Internal(internal) if internal.node < self.ops.len() => {
+                        Some(internal.node)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut marks = vec![Mark::New; self.ops.len()];
+        for root in 0..self.ops.len() {
+            if marks[root] != Mark::New {
+                continue;
+            }
+            // Every entry is a node on the current path and the dependencies left to visit.
+            marks[root] = Mark::OnPath;
+            let mut path = vec![(root, dependencies(root))];
+            while let Some((node, pending)) = path.last_mut() {
+                match pending.pop() {
+                    Some(next) => match marks[next] {
+                        Mark::OnPath => return false,
+                        Mark::New => {
+                            marks[next] = Mark::OnPath;
+                            path.push((next, dependencies(next)));
+                        }
+                        Mark::Done => {}
+                    },
+                    None => {
+                        marks[*node] = Mark::Done;
+                        path.pop();
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// The 64 bit FNV-1a hash, used for [Graph::fingerprint] because it never changes.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    /// Feeds `value` as a little-endian `u64`, so the hash doesn't depend on the width or
+    /// byte order of `usize` on the host.
+    fn usize(&mut self, value: usize) {
+        self.write(&(value as u64).to_le_bytes());
+    }
+
+    fn usizes(&mut self, values: &[usize]) {
+        self.usize(values.len());
+        for &value in values {
+            self.usize(value);
+        }
+    }
+
+    fn pairs(&mut self, pairs: &[(usize, usize)]) {
+        self.usize(pairs.len());
+        for &(a, b) in pairs {
+            self.usize(a);
+            self.usize(b);
+        }
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
 }
 
 pub trait Backend {
@@ -128,6 +1414,11 @@ pub trait Backend {
     type Error;
 
     /// Generates the initial state for a graph.
+    ///
+    /// Implementations must be deterministic in `rng`: two calls with the same graph and rngs in
+    /// the same state must produce bit-identical states, so experiments can be reproduced by
+    /// seeding the rng. In particular the rng must be the only source of randomness, and it must
+    /// be consumed in an order which only depends on the graph.
     fn state<R>(&self, graph: &Graph, rng: R) -> Result<Self::State, Self::Error>
     where
         R: RngCore;
@@ -141,10 +1432,105 @@ pub trait Backend {
         tensor: Input,
     ) -> Result<(Self::Tensor, Self::InternalStorage), Self::Error>;
 
+    /// Gets the outputs of solving several requested tensors of the same graph.
+    ///
+    /// The default implementation calls [Backend::forward] once per output, so shared parts of
+    /// the graph are computed again for each output. Backends should override it to compute
+    /// them only once.
+    fn forward_many(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        outputs: &[Input],
+    ) -> Result<Vec<Self::Tensor>, Self::Error> {
+        outputs
+            .iter()
+            .map(|output| {
+                self.forward(graph, state, inputs, output.clone())
+                    .map(|(tensor, _)| tensor)
+            })
+            .collect()
+    }
+
+    /// Like [Backend::forward], but for training, so stochastic ops like [Op::Dropout] are
+    /// active instead of being the identity.
+    ///
+    /// Backends draw the randomness from their own rng. The default implementation is
+    /// [Backend::forward], which is only correct for graphs without stochastic ops.
+    fn forward_train(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+    ) -> Result<(Self::Tensor, Self::InternalStorage), Self::Error> {
+        self.forward(graph, state, inputs, tensor)
+    }
+
+    /// Like [Backend::forward_train], but draws the randomness of stochastic ops from `rng`
+    /// instead of the backend, so passes with identically seeded rngs are identical.
+    ///
+    /// The default implementation ignores `rng` and uses [Backend::forward_train].
+    fn forward_stochastic<R>(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+        _rng: &mut R,
+    ) -> Result<(Self::Tensor, Self::InternalStorage), Self::Error>
+    where
+        R: RngCore,
+    {
+        self.forward_train(graph, state, inputs, tensor)
+    }
+
+    /// Applies the changes a training forward pass makes to the state, such as updating the
+    /// running statistics of [Op::BatchNorm], using the storage it returned.
+    ///
+    /// [Tensor::gradient_descent] calls it after every forward pass. The default implementation
+    /// does nothing, for backends whose state only changes through [Backend::train].
+    fn update_state(
+        &self,
+        _graph: &Graph,
+        _state: &mut Self::State,
+        _internal: &Self::InternalStorage,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Like [Backend::forward], but keeps the [Backend::InternalStorage] in `cache`, keyed by
+    /// the [Graph::fingerprint] of `graph`, so that a later call with the same graph and inputs
+    /// can reuse it instead of computing everything again.
+    ///
+    /// A cache made for a graph with a different fingerprint is discarded. The cache doesn't
+    /// track the state though: after the state changes, such as with [Backend::train], the
+    /// cache is stale and must be reset to [None]. The default implementation can't tell
+    /// whether the inputs changed, so it always recomputes and replaces the cache.
+    fn forward_cached(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        inputs: &Self::TensorDict,
+        tensor: Input,
+        cache: &mut Option<(u64, Self::InternalStorage)>,
+    ) -> Result<Self::Tensor, Self::Error> {
+        self.forward(graph, state, inputs, tensor)
+            .map(|(output, internal)| {
+                *cache = Some((graph.fingerprint(), internal));
+                output
+            })
+    }
+
     /// Propagates a delta from the output back to the input via chain rule
     /// and produces a `Delta` that can be used to update the graph
     /// with an optimizer. The `Delta` contains all the dE/dx of all internal
     /// variables.
+    ///
+    /// Reductions like [Op::Sum] and [Op::Mean] produce fewer elements than they consume, so
+    /// their incoming delta must be broadcast back across every reduced element (and divided by
+    /// the number of elements for [Op::Mean]) to get the delta of their input.
     fn backward(
         &self,
         graph: &Graph,
@@ -155,6 +1541,116 @@ pub trait Backend {
         output_delta: Self::Tensor,
     ) -> Result<Self::Delta, Self::Error>;
 
+    /// Like [Backend::backward], but seeds a delta at each of several outputs and sums all their
+    /// gradients into one `Delta`, such as for a loss made of several heads.
+    ///
+    /// `internal` must hold the computations of every output, for example the storage cached
+    /// by solving each of them with [Backend::forward_cached] and the same cache. The default
+    /// implementation runs [Backend::backward] for each output and accumulates with
+    /// [Backend::accumulate_delta].
+    fn backward_many(
+        &self,
+        graph: &Graph,
+        state: &Self::State,
+        internal: &Self::InternalStorage,
+        inputs: &Self::TensorDict,
+        outputs: &[(Input, Self::Tensor)],
+    ) -> Result<Self::Delta, Self::Error>
+    where
+        Self::Tensor: Clone,
+        Self::Delta: Default + for<'a> AddAssign<&'a Self::Delta>,
+    {
+        let mut acc = self.zero_delta(graph);
+        for (output, output_delta) in outputs {
+            let delta = self.backward(
+                graph,
+                state,
+                internal,
+                inputs,
+                output.clone(),
+                output_delta.clone(),
+            )?;
+            self.accumulate_delta(&mut acc, &delta);
+        }
+        Ok(acc)
+    }
+
+    /// Creates a delta which doesn't change anything when trained with, to start accumulating
+    /// deltas into with [Backend::accumulate_delta].
+    ///
+    /// The default implementation is the [Default] delta.
+    fn zero_delta(&self, _graph: &Graph) -> Self::Delta
+    where
+        Self::Delta: Default,
+    {
+        Self::Delta::default()
+    }
+
+    /// Adds the gradients of `other` into `acc`, such as to train once over a whole mini-batch.
+    ///
+    /// The default implementation uses the [AddAssign] impl of the delta.
+    fn accumulate_delta(&self, acc: &mut Self::Delta, other: &Self::Delta)
+    where
+        Self::Delta: for<'a> AddAssign<&'a Self::Delta>,
+    {
+        *acc += other;
+    }
+
     /// Applies a delta to the graph's state.
+    ///
+    /// Non-trainable nodes such as [Op::Const] must be left untouched. Nodes frozen with
+    /// [Graph::freeze] are only left untouched because [Backend::backward] doesn't produce a
+    /// delta for them, see [Backend::train_unfrozen] for deltas which may have entries for them.
     fn train(&self, state: &mut Self::State, delta: &Self::Delta) -> Result<(), Self::Error>;
+
+    /// Like [Backend::train], but leaves the nodes frozen in `graph` untouched even when the
+    /// delta has entries for them, such as a delta built by hand or for another graph.
+    ///
+    /// The default implementation is [Backend::train], which is only correct for deltas without
+    /// entries for frozen nodes.
+    fn train_unfrozen(
+        &self,
+        _graph: &Graph,
+        state: &mut Self::State,
+        delta: &Self::Delta,
+    ) -> Result<(), Self::Error> {
+        self.train(state, delta)
+    }
+
+    /// Same as [Backend::train_unfrozen], but also returns the L2 norm of the update applied to
+    /// every trained node, keyed by node, to monitor training.
+    ///
+    /// The default implementation trains with [Backend::train_unfrozen] and returns an empty map,
+    /// since only the backend can look into its delta.
+    fn train_logged(
+        &self,
+        graph: &Graph,
+        state: &mut Self::State,
+        delta: &Self::Delta,
+    ) -> Result<HashMap<usize, f32>, Self::Error> {
+        self.train_unfrozen(graph, state, delta)
+            .map(|()| HashMap::new())
+    }
+
+    /// Serializes the state, such as to resume training later with [Backend::load_state].
+    ///
+    /// The default implementation fails with [DeepError::Unsupported], for backends without
+    /// serialization.
+    fn save_state(&self, _state: &Self::State) -> Result<Vec<u8>, Self::Error>
+    where
+        Self::Error: From<DeepError>,
+    {
+        Err(DeepError::Unsupported("save_state").into())
+    }
+
+    /// Deserializes a state serialized with [Backend::save_state].
+    ///
+    /// The default implementation fails with [DeepError::Unsupported], like
+    /// [Backend::save_state].
+    fn load_state(&self, _bytes: &[u8]) -> Result<Self::State, Self::Error>
+    where
+        Self::Error: From<DeepError>,
+    {
+        Err(DeepError::Unsupported("load_state").into())
+    }
 }
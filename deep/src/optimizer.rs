@@ -0,0 +1,42 @@
+use crate::{Backend, Graph};
+use std::collections::HashMap;
+
+/// Updates the state of a graph from the delta produced by [Backend::backward].
+///
+/// The delta points in the direction which increases the loss, so an optimizer must step against
+/// it. Optimizers are `&mut self` so they can keep their own data (like momentum) between steps.
+pub trait Optimizer<B>
+where
+    B: Backend,
+{
+    /// Applies one optimization step to the `state` of `graph` using `delta`.
+    fn apply(
+        &mut self,
+        backend: &B,
+        graph: &Graph,
+        state: &mut B::State,
+        delta: &B::Delta,
+    ) -> Result<(), B::Error>;
+
+    /// Same as [Optimizer::apply], but also returns the L2 norm of the update applied to every
+    /// trained node, keyed by node, like [Backend::train_logged].
+    ///
+    /// The default implementation applies the step with [Optimizer::apply] and returns an empty
+    /// map. Optimizers should override it to train with [Backend::train_logged] instead.
+    fn apply_logged(
+        &mut self,
+        backend: &B,
+        graph: &Graph,
+        state: &mut B::State,
+        delta: &B::Delta,
+    ) -> Result<HashMap<usize, f32>, B::Error> {
+        self.apply(backend, graph, state, delta)
+            .map(|()| HashMap::new())
+    }
+
+    /// Changes the learning rate used by the following steps, which lets a
+    /// [Schedule](crate::Schedule) drive it.
+    ///
+    /// Optimizers without a learning rate ignore it.
+    fn set_learning_rate(&mut self, _learning_rate: f32) {}
+}
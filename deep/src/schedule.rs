@@ -0,0 +1,48 @@
+/// Decides the learning rate to use at every training step, so it can change over time.
+///
+/// [Tensor::gradient_descent_scheduled](crate::Tensor::gradient_descent_scheduled) passes it to
+/// the [Optimizer](crate::Optimizer) before every step.
+pub trait Schedule {
+    /// The learning rate for the step with index `step`, counting from `0`.
+    fn lr(&self, step: usize) -> f32;
+}
+
+/// Always uses the same learning rate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Constant(pub f32);
+
+impl Schedule for Constant {
+    fn lr(&self, _step: usize) -> f32 {
+        self.0
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every `step_size` steps, starting from `base`.
+///
+/// A `step_size` of `0` never decays, so the learning rate stays at `base`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StepDecay {
+    pub base: f32,
+    pub gamma: f32,
+    pub step_size: usize,
+}
+
+impl Schedule for StepDecay {
+    fn lr(&self, step: usize) -> f32 {
+        let decays = step.checked_div(self.step_size).unwrap_or(0);
+        self.base * self.gamma.powi(decays as i32)
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every step, starting from `base`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ExponentialDecay {
+    pub base: f32,
+    pub gamma: f32,
+}
+
+impl Schedule for ExponentialDecay {
+    fn lr(&self, step: usize) -> f32 {
+        self.base * self.gamma.powi(step as i32)
+    }
+}
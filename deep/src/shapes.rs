@@ -0,0 +1,257 @@
+//! Shape inference which doesn't need a [Backend](crate::Backend), so shape mismatches can be
+//! found before running anything.
+
+use crate::{DeepError, Graph, Input, Op};
+use std::collections::HashMap;
+
+/// Computes the shape of the output of every op in the graph, given the shapes of the feeds.
+///
+/// Elementwise ops with two inputs broadcast them like NumPy does, reductions produce scalars
+/// and [Op::SumAxis] drops the reduced axis.
+///
+/// Only the first output of every op is returned, use [infer_output_shapes] for all of them.
+pub fn infer_shapes(
+    graph: &Graph,
+    feed_shapes: &HashMap<String, Vec<usize>>,
+) -> Result<Vec<Vec<usize>>, DeepError> {
+    infer_output_shapes(graph, feed_shapes).map(|shapes| {
+        shapes
+            .into_iter()
+            .map(|mut outputs| outputs.swap_remove(0))
+            .collect()
+    })
+}
+
+/// Like [infer_shapes], but computes the shapes of all the outputs of every op.
+///
+/// Fails like [Graph::validate] and [Graph::topological_order] if an input refers to a node
+/// which doesn't exist or doesn't come before the op using it.
+pub fn infer_output_shapes(
+    graph: &Graph,
+    feed_shapes: &HashMap<String, Vec<usize>>,
+) -> Result<Vec<Vec<Vec<usize>>>, DeepError> {
+    // Both checks are needed for every input to index a shape which was already computed.
+    graph.validate()?;
+    graph.topological_order()?;
+    let mut shapes: Vec<Vec<Vec<usize>>> = Vec::with_capacity(graph.ops.len());
+    for (node, op) in graph.ops.iter().enumerate() {
+        let input_shapes = op
+            .inputs()
+            .into_iter()
+            .map(|input| match input {
+                Input::Feed(name) => feed_shapes
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| DeepError::UnknownFeed(name.clone())),
+                Input::Internal(internal) => Ok(shapes[internal.node][internal.output].clone()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        shapes.push(op_shapes(node, op, input_shapes)?);
+    }
+    Ok(shapes)
+}
+
+/// Computes the shapes of the outputs of the op at `node` from the shapes of its inputs.
+fn op_shapes(
+    node: usize,
+    op: &Op,
+    input_shapes: Vec<Vec<usize>>,
+) -> Result<Vec<Vec<usize>>, DeepError> {
+    let incompatible = || DeepError::ShapeMismatch {
+        node,
+        shapes: input_shapes.clone(),
+    };
+    let check_axis = |axis: usize| {
+        if axis < input_shapes[0].len() {
+            Ok(())
+        } else {
+            Err(incompatible())
+        }
+    };
+
+    let shape = match op {
+        Op::Const(shape, _) | Op::TrainConst(shape, _) | Op::TrainInit(shape, _) => shape.clone(),
+        Op::Add(..)
+        | Op::Sub(..)
+        | Op::Mul(..)
+        | Op::Div(..)
+        | Op::Max(..)
+        | Op::Min(..)
+        | Op::SquaredDiff(..) => {
+            broadcast(&input_shapes[0], &input_shapes[1]).ok_or_else(incompatible)?
+        }
+        Op::Square(..)
+        | Op::Neg(..)
+        | Op::Exp(..)
+        | Op::Log(..)
+        | Op::Sqrt(..)
+        | Op::Tanh(..)
+        | Op::Sigmoid(..)
+        | Op::Relu(..)
+        | Op::ReluThreshold(..)
+        | Op::Pow(..)
+        | Op::Clamp(..)
+        | Op::Abs(..)
+        | Op::Dropout(..)
+        | Op::Reciprocal(..)
+        | Op::Sin(..)
+        | Op::Cos(..)
+        | Op::Gelu(..)
+        | Op::LeakyRelu(..) => input_shapes[0].clone(),
+        Op::Sum(..) | Op::Mean(..) => vec![],
+        Op::SumAxis(_, axis) => {
+            check_axis(*axis)?;
+            let mut shape = input_shapes[0].clone();
+            shape.remove(*axis);
+            shape
+        }
+        Op::Softmax(_, axis) | Op::LogSoftmax(_, axis) => {
+            check_axis(*axis)?;
+            input_shapes[0].clone()
+        }
+        Op::Reshape(_, shape) => {
+            let count = |shape: &[usize]| shape.iter().product::<usize>();
+            if count(shape) != count(&input_shapes[0]) {
+                return Err(incompatible());
+            }
+            shape.clone()
+        }
+        Op::Broadcast(_, shape) => {
+            if broadcast(&input_shapes[0], shape).as_ref() != Some(shape) {
+                return Err(incompatible());
+            }
+            shape.clone()
+        }
+        Op::Concat(_, axis) => {
+            let (first, rest) = input_shapes.split_first().ok_or_else(incompatible)?;
+            check_axis(*axis)?;
+            let mut shape = first.clone();
+            for other in rest {
+                let matches = other.len() == shape.len()
+                    && (0..shape.len()).all(|i| i == *axis || other[i] == shape[i]);
+                if !matches {
+                    return Err(incompatible());
+                }
+                shape[*axis] += other[*axis];
+            }
+            shape
+        }
+        Op::Stack(_, axis) => {
+            let (first, rest) = input_shapes.split_first().ok_or_else(incompatible)?;
+            if *axis > first.len() || rest.iter().any(|other| other != first) {
+                return Err(incompatible());
+            }
+            let mut shape = first.clone();
+            shape.insert(*axis, input_shapes.len());
+            shape
+        }
+        Op::Slice(_, ranges) => {
+            let fits = ranges.len() == input_shapes[0].len()
+                && ranges
+                    .iter()
+                    .zip(&input_shapes[0])
+                    .all(|(&(start, end), &len)| start <= end && end <= len);
+            if !fits {
+                return Err(incompatible());
+            }
+            ranges.iter().map(|(start, end)| end - start).collect()
+        }
+        Op::Pad(_, padding, _) => {
+            if padding.len() != input_shapes[0].len() {
+                return Err(incompatible());
+            }
+            padding
+                .iter()
+                .zip(&input_shapes[0])
+                .map(|(&(before, after), &len)| before + len + after)
+                .collect()
+        }
+        Op::Transpose(_, a, b) => {
+            check_axis(*a)?;
+            check_axis(*b)?;
+            let mut shape = input_shapes[0].clone();
+            shape.swap(*a, *b);
+            shape
+        }
+        Op::MatMul(..) => match (&input_shapes[0][..], &input_shapes[1][..]) {
+            (&[n, k1], &[k2, m]) if k1 == k2 => vec![n, m],
+            _ => return Err(incompatible()),
+        },
+        Op::Conv2d {
+            stride, padding, ..
+        } => match (&input_shapes[0][..], &input_shapes[1][..]) {
+            (&[n, c, h, w], &[o, kc, kh, kw]) if c == kc && stride.0 > 0 && stride.1 > 0 => {
+                let out = |size: usize, padding: usize, kernel: usize, stride: usize| {
+                    (size + 2 * padding)
+                        .checked_sub(kernel)
+                        .map(|room| room / stride + 1)
+                };
+                match (
+                    out(h, padding.0, kh, stride.0),
+                    out(w, padding.1, kw, stride.1),
+                ) {
+                    (Some(oh), Some(ow)) => vec![n, o, oh, ow],
+                    _ => return Err(incompatible()),
+                }
+            }
+            _ => return Err(incompatible()),
+        },
+        Op::MaxPool2d { kernel, stride, .. } => match input_shapes[0][..] {
+            [n, c, h, w] if stride.0 > 0 && stride.1 > 0 => {
+                let out = |size: usize, kernel: usize, stride: usize| {
+                    size.checked_sub(kernel).map(|room| room / stride + 1)
+                };
+                match (out(h, kernel.0, stride.0), out(w, kernel.1, stride.1)) {
+                    (Some(oh), Some(ow)) => vec![n, c, oh, ow],
+                    _ => return Err(incompatible()),
+                }
+            }
+            _ => return Err(incompatible()),
+        },
+        Op::Embedding { .. } => match &input_shapes[0][..] {
+            &[_, features] => {
+                let mut shape = input_shapes[1].clone();
+                shape.push(features);
+                shape
+            }
+            _ => return Err(incompatible()),
+        },
+        Op::BatchNorm { .. } => match input_shapes[0].split_first() {
+            Some((_, features)) if input_shapes[1] == features && input_shapes[2] == features => {
+                let features = features.to_vec();
+                return Ok(vec![input_shapes[0].clone(), features.clone(), features]);
+            }
+            _ => return Err(incompatible()),
+        },
+        Op::LayerNorm { .. } => match input_shapes[0].last() {
+            Some(&features) if input_shapes[1] == [features] && input_shapes[2] == [features] => {
+                input_shapes[0].clone()
+            }
+            _ => return Err(incompatible()),
+        },
+        Op::SplitAt(_, index) => match input_shapes[0].split_first() {
+            Some((&rows, rest)) if *index <= rows => {
+                let part = |rows| std::iter::once(rows).chain(rest.iter().copied()).collect();
+                return Ok(vec![part(*index), part(rows - index)]);
+            }
+            _ => return Err(incompatible()),
+        },
+    };
+    Ok(vec![shape])
+}
+
+/// Broadcasts two shapes together by aligning their last axes, where an axis of size 1
+/// stretches to match the other one.
+fn broadcast(a: &[usize], b: &[usize]) -> Option<Vec<usize>> {
+    let len = a.len().max(b.len());
+    let axis =
+        |shape: &[usize], i: usize| (i + shape.len()).checked_sub(len).map_or(1, |i| shape[i]);
+    (0..len)
+        .map(|i| match (axis(a, i), axis(b, i)) {
+            (x, y) if x == y => Some(x),
+            (1, y) => Some(y),
+            (x, 1) => Some(x),
+            _ => None,
+        })
+        .collect()
+}
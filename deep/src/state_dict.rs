@@ -0,0 +1,74 @@
+use crate::{Backend, DeepError, Graph, Op};
+use rand_core::RngCore;
+use std::collections::HashMap;
+
+/// Access to the parameters in the state of a [Backend] by node, for backends whose state keeps
+/// every parameter as a [Backend::Tensor].
+pub trait StateDict: Backend {
+    /// Views the state of `graph` as the current value of every trainable op
+    /// ([Op::TrainConst] and [Op::TrainInit]), keyed by node, so parameters can be inspected or
+    /// copied between graphs by node.
+    ///
+    /// Buffers of other ops, such as the running statistics of [Op::BatchNorm], are left out.
+    fn state_dict<'a>(
+        &self,
+        graph: &Graph,
+        state: &'a Self::State,
+    ) -> HashMap<usize, &'a Self::Tensor>;
+
+    /// Same as [StateDict::state_dict], but the values can be edited in place.
+    fn state_dict_mut<'a>(
+        &self,
+        graph: &Graph,
+        state: &'a mut Self::State,
+    ) -> HashMap<usize, &'a mut Self::Tensor>;
+
+    /// Generates a state for `dst_graph` like [Backend::state], but with the values of the
+    /// trainable ops ([Op::TrainConst] and [Op::TrainInit]) copied from `src_state`, such as to
+    /// fine-tune a trained graph.
+    ///
+    /// Trainable ops are matched by their position in the [Graph::topological_order] of each
+    /// graph, and values are only copied when the shapes of the ops match. The other ones keep
+    /// their fresh initialization from `rng`. The default implementation copies the values
+    /// through [StateDict::state_dict] and [StateDict::state_dict_mut].
+    fn transfer_state<R>(
+        &self,
+        src_graph: &Graph,
+        src_state: &Self::State,
+        dst_graph: &Graph,
+        rng: R,
+    ) -> Result<Self::State, Self::Error>
+    where
+        R: RngCore,
+        Self::Tensor: Clone,
+        Self::Error: From<DeepError>,
+    {
+        let trainable = |graph: &Graph| -> Result<Vec<(usize, Vec<usize>)>, DeepError> {
+            Ok(graph
+                .topological_order()?
+                .into_iter()
+                .filter_map(|node| match &graph.ops()[node] {
+                    Op::TrainConst(shape, _) | Op::TrainInit(shape, _) => {
+                        Some((node, shape.clone()))
+                    }
+                    _ => None,
+                })
+                .collect())
+        };
+        let pairs = trainable(src_graph)?
+            .into_iter()
+            .zip(trainable(dst_graph)?)
+            .filter(|((_, src_shape), (_, dst_shape))| src_shape == dst_shape);
+
+        let mut dst_state = self.state(dst_graph, rng)?;
+        let src_dict = self.state_dict(src_graph, src_state);
+        let mut dst_dict = self.state_dict_mut(dst_graph, &mut dst_state);
+        for ((src, _), (dst, _)) in pairs {
+            if let (Some(&value), Some(slot)) = (src_dict.get(&src), dst_dict.get_mut(&dst)) {
+                **slot = value.clone();
+            }
+        }
+        drop(dst_dict);
+        Ok(dst_state)
+    }
+}
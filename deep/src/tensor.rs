@@ -1,7 +1,8 @@
-use crate::{Backend, Graph, Input, Internal, Op};
+use crate::{Backend, Graph, InitKind, Input, Op, Optimizer, Schedule};
 use rand_core::RngCore;
-use std::cell::RefCell;
-use std::ops::{Add, Sub};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
 use std::rc::Rc;
 
 /// Stores the operations done to arrive at the final Tensor value in its [Graph]
@@ -17,19 +18,372 @@ impl Tensor {
         graph.ops.push(Op::TrainConst(shape, value));
         Tensor {
             graph: Rc::new(RefCell::new(graph)),
-            input: Input::Internal(Internal { node: 0, output: 0 }),
+            input: Input::internal(0),
         }
     }
 
+    /// Creates a trainable tensor whose elements are initialized according to `kind`.
+    pub fn train_init(shape: Vec<usize>, kind: InitKind) -> Self {
+        let mut graph: Graph = Default::default();
+        graph.ops.push(Op::TrainInit(shape, kind));
+        Tensor {
+            graph: Rc::new(RefCell::new(graph)),
+            input: Input::internal(0),
+        }
+    }
+
+    /// Creates a constant tensor which is not affected by training.
+    pub fn constant(shape: Vec<usize>, value: f64) -> Self {
+        let mut graph: Graph = Default::default();
+        graph.ops.push(Op::Const(shape, value));
+        Tensor {
+            graph: Rc::new(RefCell::new(graph)),
+            input: Input::internal(0),
+        }
+    }
+
+    /// Creates a non-trainable scalar constant.
+    pub fn scalar(value: f64) -> Self {
+        Self::constant(vec![], value)
+    }
+
+    /// Wraps a graph built by hand, for example with [Graph::append], into a tensor which refers
+    /// to `input` in it.
+    pub fn from_graph(graph: Graph, input: Input) -> Self {
+        Tensor {
+            graph: Rc::new(RefCell::new(graph)),
+            input,
+        }
+    }
+
+    /// Borrows the [Graph] this tensor is computed from.
+    pub fn graph(&self) -> Ref<'_, Graph> {
+        self.graph.borrow()
+    }
+
+    /// The [Input] which this tensor refers to in its [Graph].
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    /// Creates a copy of the tensor with its own copy of the [Graph].
+    ///
+    /// Unlike [Clone], appending ops to the original tensor's graph doesn't affect the detached
+    /// tensor and the other way around.
+    pub fn detach(&self) -> Self {
+        Tensor {
+            graph: Rc::new(RefCell::new(self.graph.borrow().clone())),
+            input: self.input.clone(),
+        }
+    }
+
+    /// Checks if both tensors are computed from the very same [Graph] (not just an equal one).
+    pub fn shares_graph(&self, other: &Tensor) -> bool {
+        Rc::ptr_eq(&self.graph, &other.graph)
+    }
+
     pub fn squared(&self) -> Self {
-        let graph = self.graph.clone();
-        let node = graph.borrow_mut().append(Op::Square(self.input.clone()));
-        Self {
-            graph,
-            input: Input::Internal(Internal { node, output: 0 }),
+        apply1_1(self, Op::Square)
+    }
+
+    /// Computes the elementwise natural exponential of the tensor.
+    pub fn exp(&self) -> Self {
+        apply1_1(self, Op::Exp)
+    }
+
+    /// Computes the elementwise natural logarithm of the tensor.
+    pub fn ln(&self) -> Self {
+        apply1_1(self, Op::Log)
+    }
+
+    /// Computes the elementwise square root of the tensor.
+    pub fn sqrt(&self) -> Self {
+        apply1_1(self, Op::Sqrt)
+    }
+
+    /// Computes the elementwise hyperbolic tangent of the tensor.
+    pub fn tanh(&self) -> Self {
+        apply1_1(self, Op::Tanh)
+    }
+
+    /// Computes the elementwise logistic sigmoid of the tensor.
+    pub fn sigmoid(&self) -> Self {
+        apply1_1(self, Op::Sigmoid)
+    }
+
+    /// Computes the elementwise rectified linear unit of the tensor.
+    pub fn relu(&self) -> Self {
+        apply1_1(self, Op::Relu)
+    }
+
+    /// Computes the elementwise maximum of the tensor and `threshold`.
+    pub fn relu_threshold(&self, threshold: f64) -> Self {
+        apply1_1(self, |a| Op::ReluThreshold(a, threshold))
+    }
+
+    /// Raises every element of the tensor to the power `exponent`.
+    pub fn powf(&self, exponent: f64) -> Self {
+        apply1_1(self, |a| Op::Pow(a, exponent))
+    }
+
+    /// Computes the elementwise maximum of this tensor and `other`.
+    pub fn maximum(self, other: Tensor) -> Self {
+        merge2_1(self, other, Op::Max)
+    }
+
+    /// Computes the elementwise minimum of this tensor and `other`.
+    pub fn minimum(self, other: Tensor) -> Self {
+        merge2_1(self, other, Op::Min)
+    }
+
+    /// Sums all the elements of the tensor into a scalar.
+    pub fn sum(&self) -> Self {
+        apply1_1(self, Op::Sum)
+    }
+
+    /// Averages all the elements of the tensor into a scalar.
+    pub fn mean(&self) -> Self {
+        apply1_1(self, Op::Mean)
+    }
+
+    /// Sums the elements of the tensor along `axis`.
+    pub fn sum_axis(&self, axis: usize) -> Self {
+        apply1_1(self, |a| Op::SumAxis(a, axis))
+    }
+
+    /// Reshapes the tensor into `shape`.
+    pub fn reshape(&self, shape: Vec<usize>) -> Self {
+        apply1_1(self, |a| Op::Reshape(a, shape))
+    }
+
+    /// Swaps the axes `axis_a` and `axis_b` of the tensor.
+    pub fn transpose(&self, axis_a: usize, axis_b: usize) -> Self {
+        apply1_1(self, |a| Op::Transpose(a, axis_a, axis_b))
+    }
+
+    /// Computes the matrix product of this tensor (on the left) and `other` (on the right).
+    pub fn matmul(self, other: Tensor) -> Self {
+        merge2_1(self, other, Op::MatMul)
+    }
+
+    /// 2D convolution of the tensor by `kernel`, both in the NCHW layout, see [Op::Conv2d].
+    pub fn conv2d(self, kernel: Tensor, stride: (usize, usize), padding: (usize, usize)) -> Self {
+        merge2_1(self, kernel, |input, kernel| Op::Conv2d {
+            input,
+            kernel,
+            stride,
+            padding,
+        })
+    }
+
+    /// 2D max pooling of the tensor in the NCHW layout, see [Op::MaxPool2d].
+    pub fn max_pool2d(&self, kernel: (usize, usize), stride: (usize, usize)) -> Self {
+        apply1_1(self, |input| Op::MaxPool2d {
+            input,
+            kernel,
+            stride,
+        })
+    }
+
+    /// Looks up the rows of `table` selected by `indices`, see [Op::Embedding].
+    pub fn embedding(table: Tensor, indices: Tensor) -> Self {
+        merge2_1(table, indices, |table, indices| Op::Embedding {
+            table,
+            indices,
+        })
+    }
+
+    /// Batch normalization of the tensor, scaled by `gamma` and shifted by `beta`, see
+    /// [Op::BatchNorm].
+    pub fn batch_norm(self, gamma: Tensor, beta: Tensor) -> Self {
+        merge_n_1(vec![self, gamma, beta], |inputs| {
+            let mut inputs = inputs.into_iter();
+            let mut next = || inputs.next().unwrap();
+            Op::BatchNorm {
+                input: next(),
+                gamma: next(),
+                beta: next(),
+            }
+        })
+    }
+
+    /// Layer normalization of the tensor over its last axis, scaled by `gamma` and shifted by
+    /// `beta`, see [Op::LayerNorm].
+    pub fn layer_norm(self, gamma: Tensor, beta: Tensor, eps: f64) -> Self {
+        merge_n_1(vec![self, gamma, beta], |inputs| {
+            let mut inputs = inputs.into_iter();
+            let mut next = || inputs.next().unwrap();
+            Op::LayerNorm {
+                input: next(),
+                gamma: next(),
+                beta: next(),
+                eps,
+            }
+        })
+    }
+
+    /// Splits the tensor along its first axis at `index`, returning the part before `index` and
+    /// the part after it.
+    pub fn split_at(&self, index: usize) -> (Self, Self) {
+        let split = apply1_1(self, |a| Op::SplitAt(a, index));
+        (split.output(0), split.output(1))
+    }
+
+    /// Selects another output of the op which produces this tensor.
+    ///
+    /// Panics if the tensor is a feed, since feeds aren't produced by an op.
+    pub fn output(&self, output: usize) -> Self {
+        match &self.input {
+            Input::Internal(internal) => Tensor {
+                graph: self.graph.clone(),
+                input: Input::internal_out(internal.node, output),
+            },
+            Input::Feed(name) => panic!("feed \"{}\" has no outputs to select from", name),
+        }
+    }
+
+    /// Freezes the trainable op producing this tensor with [Graph::freeze], so training leaves
+    /// it unchanged.
+    ///
+    /// Panics if the tensor is a feed.
+    pub fn freeze(&self) {
+        self.graph.borrow_mut().freeze(self.node("freeze"));
+    }
+
+    /// Makes the op producing this tensor trainable again after [Tensor::freeze].
+    ///
+    /// Panics if the tensor is a feed.
+    pub fn unfreeze(&self) {
+        self.graph.borrow_mut().unfreeze(self.node("unfreeze"));
+    }
+
+    fn node(&self, action: &str) -> usize {
+        match &self.input {
+            Input::Internal(internal) => internal.node,
+            Input::Feed(name) => {
+                panic!("feed \"{}\" can't {} since it isn't trained", name, action)
+            }
         }
     }
 
+    /// Limits every element of the tensor to the range from `min` to `max`.
+    pub fn clamp(&self, min: f64, max: f64) -> Self {
+        apply1_1(self, |a| Op::Clamp(a, min, max))
+    }
+
+    /// Computes the elementwise absolute value of the tensor.
+    pub fn abs(&self) -> Self {
+        apply1_1(self, Op::Abs)
+    }
+
+    /// Computes the softmax of the tensor along `axis`.
+    pub fn softmax(&self, axis: usize) -> Self {
+        apply1_1(self, |a| Op::Softmax(a, axis))
+    }
+
+    /// Computes the logarithm of the softmax of the tensor along `axis`.
+    pub fn log_softmax(&self, axis: usize) -> Self {
+        apply1_1(self, |a| Op::LogSoftmax(a, axis))
+    }
+
+    /// Adds all the tensors together, merging their graphs from left to right.
+    ///
+    /// Returns [None] if there are no tensors.
+    pub fn sum_of(tensors: impl IntoIterator<Item = Tensor>) -> Option<Self> {
+        tensors.into_iter().reduce(|total, tensor| total + tensor)
+    }
+
+    /// The cross-entropy between the predicted `logits` and the `targets` probabilities, both
+    /// of shape `(batch, classes)`, summed over the batch into a scalar loss.
+    ///
+    /// It is built as `-sum(log_softmax(logits, 1) * targets)`.
+    pub fn cross_entropy(logits: Tensor, targets: Tensor) -> Self {
+        -(logits.log_softmax(1) * targets).sum()
+    }
+
+    /// The mean squared error between `pred` and `target`, a scalar loss.
+    ///
+    /// It is built as `mean((pred - target)^2)`.
+    pub fn mse(pred: Tensor, target: Tensor) -> Self {
+        (pred - target).squared().mean()
+    }
+
+    /// The sum of the squares of all the elements of `params`, scaled by `coefficient`, to add
+    /// to a loss as weight decay.
+    ///
+    /// It is built as `coefficient * (sum(p1^2) + sum(p2^2) + ...)`, or a constant `0` without
+    /// parameters. The parameters should share the graph of the loss (see
+    /// [Tensor::shares_graph]), otherwise they're merged into it as separate copies.
+    pub fn l2_regularization(params: &[Tensor], coefficient: f64) -> Self {
+        Tensor::sum_of(params.iter().map(|param| param.squared().sum())).map_or_else(
+            || Tensor::scalar(0.0),
+            |sum| sum * Tensor::scalar(coefficient),
+        )
+    }
+
+    /// Broadcasts the tensor to `shape`.
+    pub fn broadcast_to(&self, shape: Vec<usize>) -> Self {
+        apply1_1(self, |a| Op::Broadcast(a, shape))
+    }
+
+    /// Joins the tensors together along `axis`, in order.
+    ///
+    /// The graphs of the tensors are merged into the graph of the first one. Panics if there are
+    /// no tensors.
+    pub fn concat(tensors: Vec<Tensor>, axis: usize) -> Self {
+        merge_n_1(tensors, |inputs| Op::Concat(inputs, axis))
+    }
+
+    /// Stacks the tensors along a new axis inserted at `axis`, in order. All the tensors must
+    /// have the same shape.
+    ///
+    /// The graphs of the tensors are merged into the graph of the first one. Panics if there are
+    /// no tensors.
+    pub fn stack(tensors: Vec<Tensor>, axis: usize) -> Self {
+        merge_n_1(tensors, |inputs| Op::Stack(inputs, axis))
+    }
+
+    /// Extracts the subtensor given by a `(start, end)` range for each axis.
+    pub fn slice(&self, ranges: Vec<(usize, usize)>) -> Self {
+        apply1_1(self, |a| Op::Slice(a, ranges))
+    }
+
+    /// Randomly zeroes elements with probability `p` while training, see [Op::Dropout].
+    pub fn dropout(&self, p: f64) -> Self {
+        apply1_1(self, |a| Op::Dropout(a, p))
+    }
+
+    /// Reciprocal of every element, `1 / x`.
+    pub fn recip(&self) -> Self {
+        apply1_1(self, Op::Reciprocal)
+    }
+
+    /// Sine of every element, in radians.
+    pub fn sin(&self) -> Self {
+        apply1_1(self, Op::Sin)
+    }
+
+    /// Cosine of every element, in radians.
+    pub fn cos(&self) -> Self {
+        apply1_1(self, Op::Cos)
+    }
+
+    /// Pads the tensor with `value`, where `padding` holds the `(before, after)` number of elements
+    /// to add to every axis.
+    pub fn pad(&self, padding: Vec<(usize, usize)>, value: f64) -> Self {
+        apply1_1(self, |a| Op::Pad(a, padding, value))
+    }
+
+    /// Gaussian error linear unit, see [Op::Gelu].
+    pub fn gelu(&self) -> Self {
+        apply1_1(self, Op::Gelu)
+    }
+
+    /// Leaky ReLU, which multiplies negative elements by `slope`.
+    pub fn leaky_relu(&self, slope: f64) -> Self {
+        apply1_1(self, |a| Op::LeakyRelu(a, slope))
+    }
+
     /// Creates the state for the tensor.
     pub fn gen_state<B>(&self, backend: &B, rng: impl RngCore) -> Result<B::State, B::Error>
     where
@@ -48,35 +402,146 @@ impl Tensor {
     where
         B: Backend,
     {
-        backend
-            .forward(&self.graph.borrow(), state, inputs, self.input.clone())
+        self.eval_full(backend, state, inputs)
             .map(|(output, _)| output)
     }
 
+    /// Evaluate the tensor, also returning the [Backend::InternalStorage] with the intermediary
+    /// computations, which is useful for looking at the outputs of every op.
+    pub fn eval_full<B>(
+        &self,
+        backend: &B,
+        state: &B::State,
+        inputs: &B::TensorDict,
+    ) -> Result<(B::Tensor, B::InternalStorage), B::Error>
+    where
+        B: Backend,
+    {
+        backend.forward(&self.graph.borrow(), state, inputs, self.input.clone())
+    }
+
     /// Train the graph with this tensor as a loss function using gradient descent.
     ///
     /// Must be provided a way to convert the loss tensor into a `f32` and a `f32` to a tensor.
+    /// The [Optimizer] decides how the computed delta is applied to the state. The forward pass
+    /// is done with [Backend::forward_train], so stochastic ops like [Op::Dropout] are active.
     ///
     /// Returns the loss before training.
-    pub fn gradient_descent<B>(
+    pub fn gradient_descent<B, O>(
         &self,
         backend: &B,
         state: &mut B::State,
         inputs: &B::TensorDict,
-        learning_rate: f32,
+        optimizer: &mut O,
         tensor_loss: fn(B::Tensor) -> f32,
         delta_tensor: fn(f32) -> B::Tensor,
     ) -> Result<f32, B::Error>
+    where
+        B: Backend,
+        O: Optimizer<B> + ?Sized,
+    {
+        self.gradient_descent_with_delta(
+            backend,
+            state,
+            inputs,
+            optimizer,
+            tensor_loss,
+            delta_tensor,
+        )
+        .map(|(loss, _)| loss)
+    }
+
+    /// Same as [Tensor::gradient_descent], but first sets the learning rate of the optimizer to
+    /// the one `schedule` gives for `step`.
+    ///
+    /// The caller keeps the step counter, usually incrementing it after every call.
+    #[allow(clippy::too_many_arguments)]
+    pub fn gradient_descent_scheduled<B, O>(
+        &self,
+        backend: &B,
+        state: &mut B::State,
+        inputs: &B::TensorDict,
+        optimizer: &mut O,
+        schedule: &impl Schedule,
+        step: usize,
+        tensor_loss: fn(B::Tensor) -> f32,
+        delta_tensor: fn(f32) -> B::Tensor,
+    ) -> Result<f32, B::Error>
+    where
+        B: Backend,
+        O: Optimizer<B> + ?Sized,
+    {
+        optimizer.set_learning_rate(schedule.lr(step));
+        self.gradient_descent(backend, state, inputs, optimizer, tensor_loss, delta_tensor)
+    }
+
+    /// Same as [Tensor::gradient_descent], but also returns the delta which was given to the
+    /// optimizer, so that the gradients can be inspected.
+    pub fn gradient_descent_with_delta<B, O>(
+        &self,
+        backend: &B,
+        state: &mut B::State,
+        inputs: &B::TensorDict,
+        optimizer: &mut O,
+        tensor_loss: fn(B::Tensor) -> f32,
+        delta_tensor: fn(f32) -> B::Tensor,
+    ) -> Result<(f32, B::Delta), B::Error>
+    where
+        B: Backend,
+        O: Optimizer<B> + ?Sized,
+    {
+        let (loss, delta) = self.loss_delta(backend, state, inputs, tensor_loss, delta_tensor)?;
+
+        // Train the network.
+        optimizer.apply(backend, &self.graph.borrow(), state, &delta)?;
+
+        // Return the loss and the delta.
+        Ok((loss, delta))
+    }
+
+    /// Same as [Tensor::gradient_descent], but also returns the L2 norm of the update applied
+    /// to every trained node, keyed by node, as given by [Optimizer::apply_logged].
+    pub fn gradient_descent_logged<B, O>(
+        &self,
+        backend: &B,
+        state: &mut B::State,
+        inputs: &B::TensorDict,
+        optimizer: &mut O,
+        tensor_loss: fn(B::Tensor) -> f32,
+        delta_tensor: fn(f32) -> B::Tensor,
+    ) -> Result<(f32, HashMap<usize, f32>), B::Error>
+    where
+        B: Backend,
+        O: Optimizer<B> + ?Sized,
+    {
+        let (loss, delta) = self.loss_delta(backend, state, inputs, tensor_loss, delta_tensor)?;
+        optimizer
+            .apply_logged(backend, &self.graph.borrow(), state, &delta)
+            .map(|norms| (loss, norms))
+    }
+
+    /// Does the forward and backward passes of [Tensor::gradient_descent], returning the loss and
+    /// the delta to train with.
+    fn loss_delta<B>(
+        &self,
+        backend: &B,
+        state: &mut B::State,
+        inputs: &B::TensorDict,
+        tensor_loss: fn(B::Tensor) -> f32,
+        delta_tensor: fn(f32) -> B::Tensor,
+    ) -> Result<(f32, B::Delta), B::Error>
     where
         B: Backend,
     {
         // Perform the forward pass.
         let (output, internal) =
-            backend.forward(&self.graph.borrow(), state, inputs, self.input.clone())?;
+            backend.forward_train(&self.graph.borrow(), state, inputs, self.input.clone())?;
 
         // Extract the loss and compute the output delta.
         let loss = tensor_loss(output);
-        let output_delta = delta_tensor(-learning_rate * loss);
+        let output_delta = delta_tensor(loss);
+
+        backend.update_state(&self.graph.borrow(), state, &internal)?;
 
         // Propogate the output delta back through the network.
         let delta = backend.backward(
@@ -87,19 +552,26 @@ impl Tensor {
             self.input.clone(),
             output_delta,
         )?;
+        Ok((loss, delta))
+    }
+}
 
-        // Train the network.
-        backend.train(state, &delta)?;
-
-        // Return the loss.
-        Ok(loss)
+/// Clones are shallow: the clone shares the same underlying [Graph] as the original tensor, so
+/// appending an op through one of them is visible through the other one as well.
+/// Use [Tensor::detach] to get an independent copy.
+impl Clone for Tensor {
+    fn clone(&self) -> Self {
+        Tensor {
+            graph: self.graph.clone(),
+            input: self.input.clone(),
+        }
     }
 }
 
 /// Creates a Tensor with an empty [Graph], no Ops. Its value will be fetched from the
 /// [Backend::TensorDict] using the provided String as key
-impl From<&str> for Tensor {
-    fn from(s: &str) -> Tensor {
+impl From<String> for Tensor {
+    fn from(s: String) -> Tensor {
         Tensor {
             graph: Default::default(),
             input: s.into(),
@@ -107,22 +579,87 @@ impl From<&str> for Tensor {
     }
 }
 
+impl From<&str> for Tensor {
+    fn from(s: &str) -> Tensor {
+        s.to_owned().into()
+    }
+}
+
+/// Creates a non-trainable scalar constant, so literals can be used in expressions.
+impl From<f64> for Tensor {
+    fn from(value: f64) -> Tensor {
+        Tensor::scalar(value)
+    }
+}
+
+/// Creates a non-trainable scalar constant, like the [f64] conversion.
+impl From<i32> for Tensor {
+    fn from(value: i32) -> Tensor {
+        f64::from(value).into()
+    }
+}
+
+/// Appends an op which only consumes tensor a to the graph associated with a.
+/// The returned Tensor shares the graph with a
+fn apply1_1(a: &Tensor, make_op: impl FnOnce(Input) -> Op) -> Tensor {
+    let graph = a.graph.clone();
+    let node = graph
+        .borrow_mut()
+        .append_unchecked(make_op(a.input.clone()));
+    Tensor {
+        graph,
+        input: Input::internal(node),
+    }
+}
+
 /// Merges the graph associated with b tensor with the graph associated with a,
 /// appending b into the end of a.
 /// Shifts the b inputs by the length of a, so they "point" to the right place still
 /// Puts it all into a new Tensor and returns it
+///
+/// If a and b already share the same graph, nothing is merged and b's input is used as is
 fn merge2_1(a: Tensor, b: Tensor, make_op: impl Fn(Input, Input) -> Op) -> Tensor {
     let a_graph = a.graph;
     let a_input = a.input;
-    let a_with_b_merged = a_graph
-        .borrow_mut()
-        .merge_input(b.graph.borrow().clone(), b.input);
+    let a_with_b_merged = if Rc::ptr_eq(&a_graph, &b.graph) {
+        b.input
+    } else {
+        let b_graph = b.graph.borrow().clone();
+        a_graph.borrow_mut().merge_input(b_graph, b.input)
+    };
     let node = a_graph
         .borrow_mut()
-        .append(make_op(a_input, a_with_b_merged));
+        .append_unchecked(make_op(a_input, a_with_b_merged));
     Tensor {
         graph: a_graph,
-        input: Input::Internal(Internal { node, output: 0 }),
+        input: Input::internal(node),
+    }
+}
+
+/// Like [merge2_1], but for any number of tensors, which are merged in order into the graph of
+/// the first one.
+///
+/// Panics if there are no tensors.
+fn merge_n_1(tensors: Vec<Tensor>, make_op: impl FnOnce(Vec<Input>) -> Op) -> Tensor {
+    let mut tensors = tensors.into_iter();
+    let first = tensors
+        .next()
+        .expect("at least one tensor is needed to build the op");
+    let graph = first.graph;
+    let mut inputs = vec![first.input];
+    for tensor in tensors {
+        let input = if Rc::ptr_eq(&graph, &tensor.graph) {
+            tensor.input
+        } else {
+            let other = tensor.graph.borrow().clone();
+            graph.borrow_mut().merge_input(other, tensor.input)
+        };
+        inputs.push(input);
+    }
+    let node = graph.borrow_mut().append_unchecked(make_op(inputs));
+    Tensor {
+        graph,
+        input: Input::internal(node),
     }
 }
 
@@ -141,3 +678,111 @@ impl Sub for Tensor {
         merge2_1(self, rhs, Op::Sub)
     }
 }
+
+impl Add<&Tensor> for &Tensor {
+    type Output = Tensor;
+
+    fn add(self, rhs: &Tensor) -> Tensor {
+        merge2_1(self.clone(), rhs.clone(), Op::Add)
+    }
+}
+
+impl Sub<&Tensor> for &Tensor {
+    type Output = Tensor;
+
+    fn sub(self, rhs: &Tensor) -> Tensor {
+        merge2_1(self.clone(), rhs.clone(), Op::Sub)
+    }
+}
+
+/// Appends the add to the graph of `self`, merging in the graph of `rhs`, and makes `self` refer
+/// to the result.
+impl AddAssign for Tensor {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = merge2_1(self.clone(), rhs, Op::Add);
+    }
+}
+
+/// Like [AddAssign], but appends a sub.
+impl SubAssign for Tensor {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = merge2_1(self.clone(), rhs, Op::Sub);
+    }
+}
+
+impl Mul for Tensor {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        merge2_1(self, rhs, Op::Mul)
+    }
+}
+
+impl Div for Tensor {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        merge2_1(self, rhs, Op::Div)
+    }
+}
+
+impl Neg for Tensor {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        let graph = self.graph;
+        let node = graph.borrow_mut().append_unchecked(Op::Neg(self.input));
+        Self {
+            graph,
+            input: Input::internal(node),
+        }
+    }
+}
+
+impl Add<f64> for Tensor {
+    type Output = Self;
+
+    fn add(self, rhs: f64) -> Self {
+        merge2_1(self, rhs.into(), Op::Add)
+    }
+}
+
+impl Sub<f64> for Tensor {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self {
+        merge2_1(self, rhs.into(), Op::Sub)
+    }
+}
+
+impl Mul<f64> for Tensor {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        merge2_1(self, rhs.into(), Op::Mul)
+    }
+}
+
+impl Add<Tensor> for f64 {
+    type Output = Tensor;
+
+    fn add(self, rhs: Tensor) -> Tensor {
+        merge2_1(self.into(), rhs, Op::Add)
+    }
+}
+
+impl Sub<Tensor> for f64 {
+    type Output = Tensor;
+
+    fn sub(self, rhs: Tensor) -> Tensor {
+        merge2_1(self.into(), rhs, Op::Sub)
+    }
+}
+
+impl Mul<Tensor> for f64 {
+    type Output = Tensor;
+
+    fn mul(self, rhs: Tensor) -> Tensor {
+        merge2_1(self.into(), rhs, Op::Mul)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{DeepError, Graph, Input, Internal, Op, OpTy};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Walks the ops of a [Graph], see [Graph::accept].
+pub trait GraphVisitor {
+    /// Called once for every op, in order, with the node index of the op.
+    fn visit_op(&mut self, index: usize, op: &Op);
+}
+
+/// Collects the names of the feeds used by the visited ops.
+#[derive(Default)]
+pub(crate) struct FeedKeys(pub BTreeSet<String>);
+
+impl GraphVisitor for FeedKeys {
+    fn visit_op(&mut self, _index: usize, op: &Op) {
+        self.0
+            .extend(op.inputs().into_iter().filter_map(|input| match input {
+                Input::Feed(name) => Some(name.clone()),
+                Input::Internal(_) => None,
+            }));
+    }
+}
+
+/// Keeps the first [DeepError::DanglingNode] or [DeepError::DanglingOutput] found among the
+/// visited ops of `graph`.
+pub(crate) struct Validator<'a> {
+    pub graph: &'a Graph,
+    pub result: Result<(), DeepError>,
+}
+
+impl GraphVisitor for Validator<'_> {
+    fn visit_op(&mut self, _index: usize, op: &Op) {
+        if self.result.is_ok() {
+            self.result = self.graph.check_inputs(op);
+        }
+    }
+}
+
+/// Writes a DOT node for every visited op and the edges from its inputs, see
+/// [Graph::to_dot].
+#[derive(Default)]
+pub(crate) struct Dot {
+    pub nodes: String,
+    pub edges: String,
+}
+
+impl Dot {
+    pub fn escape(name: &str) -> String {
+        name.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+}
+
+impl GraphVisitor for Dot {
+    fn visit_op(&mut self, index: usize, op: &Op) {
+        // Writing into a `String` never fails, so the results are ignored.
+        let _ = writeln!(
+            self.nodes,
+            "    {} [label=\"{:?}\"];",
+            index,
+            OpTy::from(op)
+        );
+        for input in op.inputs() {
+            let _ = match input {
+                Input::Feed(name) => {
+                    writeln!(
+                        self.edges,
+                        "    \"feed_{}\" -> {};",
+                        Self::escape(name),
+                        index
+                    )
+                }
+                Input::Internal(Internal {
+                    node: from,
+                    output: 0,
+                }) => {
+                    writeln!(self.edges, "    {} -> {};", from, index)
+                }
+                Input::Internal(Internal { node: from, output }) => {
+                    writeln!(
+                        self.edges,
+                        "    {} -> {} [label=\"{}\"];",
+                        from, index, output
+                    )
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use deep::*;
+use rand_core::RngCore;
+
+/// A backend implementing only the required methods, which sums everything into a scalar.
+struct Minimal;
+
+impl Backend for Minimal {
+    type TensorDict = ();
+    type InternalStorage = ();
+    type Tensor = f32;
+    type Delta = f32;
+    type State = f32;
+    type Error = DeepError;
+
+    fn state<R>(&self, _graph: &Graph, _rng: R) -> Result<f32, DeepError>
+    where
+        R: RngCore,
+    {
+        Ok(0.0)
+    }
+
+    fn forward(
+        &self,
+        _graph: &Graph,
+        state: &f32,
+        _inputs: &(),
+        _tensor: Input,
+    ) -> Result<(f32, ()), DeepError> {
+        Ok((*state, ()))
+    }
+
+    fn backward(
+        &self,
+        _graph: &Graph,
+        _state: &f32,
+        _internal: &(),
+        _inputs: &(),
+        _tensor: Input,
+        output_delta: f32,
+    ) -> Result<f32, DeepError> {
+        Ok(output_delta)
+    }
+
+    fn train(&self, state: &mut f32, delta: &f32) -> Result<(), DeepError> {
+        *state += delta;
+        Ok(())
+    }
+}
+
+#[test]
+fn defaults_accumulate_and_train() {
+    let graph = Graph::new();
+    let mut acc = Minimal.zero_delta(&graph);
+    Minimal.accumulate_delta(&mut acc, &1.5);
+    Minimal.accumulate_delta(&mut acc, &2.0);
+    assert_eq!(acc, 3.5);
+
+    let mut state = 1.0;
+    let norms = Minimal.train_logged(&graph, &mut state, &acc).unwrap();
+    assert!(norms.is_empty());
+    assert_eq!(state, 4.5);
+}
+
+#[test]
+fn save_state_unsupported() {
+    assert_eq!(
+        Minimal.save_state(&1.0),
+        Err(DeepError::Unsupported("save_state"))
+    );
+    assert_eq!(
+        Minimal.load_state(&[]),
+        Err(DeepError::Unsupported("load_state"))
+    );
+    assert_eq!(
+        DeepError::Unsupported("save_state").to_string(),
+        "the backend doesn't support save_state"
+    );
+}
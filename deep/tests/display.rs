@@ -0,0 +1,139 @@
+use deep::*;
+
+fn internal(node: usize, output: usize) -> Input {
+    Input::Internal(Internal { node, output })
+}
+
+#[test]
+fn display_input() {
+    assert_eq!(Input::from("x").to_string(), "x");
+    assert_eq!(internal(3, 0).to_string(), "#3");
+    assert_eq!(internal(3, 1).to_string(), "#3.1");
+}
+
+#[test]
+fn display_binary_ops() {
+    let x = || Input::from("x");
+    let cases = [
+        (Op::Add(internal(0, 0), x()), "Add(#0, x)"),
+        (Op::Sub(x(), internal(1, 0)), "Sub(x, #1)"),
+        (Op::Mul(x(), x()), "Mul(x, x)"),
+        (Op::Div(internal(0, 1), x()), "Div(#0.1, x)"),
+        (Op::Max(x(), internal(2, 0)), "Max(x, #2)"),
+        (Op::Min(x(), internal(2, 0)), "Min(x, #2)"),
+        (Op::MatMul(x(), internal(4, 0)), "MatMul(x, #4)"),
+    ];
+    for (op, rendered) in &cases {
+        assert_eq!(op.to_string(), *rendered);
+    }
+}
+
+#[test]
+fn display_unary_ops() {
+    let cases = [
+        (Op::Square(internal(0, 0)), "Square(#0)"),
+        (Op::Neg(internal(0, 0)), "Neg(#0)"),
+        (Op::Exp(internal(0, 0)), "Exp(#0)"),
+        (Op::Log(internal(0, 0)), "Log(#0)"),
+        (Op::Sqrt(internal(0, 0)), "Sqrt(#0)"),
+        (Op::Tanh(internal(0, 0)), "Tanh(#0)"),
+        (Op::Sigmoid(internal(0, 0)), "Sigmoid(#0)"),
+        (Op::Relu(internal(0, 0)), "Relu(#0)"),
+        (Op::Sum(internal(0, 0)), "Sum(#0)"),
+        (Op::Mean(internal(0, 0)), "Mean(#0)"),
+        (Op::Abs(internal(0, 0)), "Abs(#0)"),
+    ];
+    for (op, rendered) in &cases {
+        assert_eq!(op.to_string(), *rendered);
+    }
+}
+
+#[test]
+fn display_parameterized_ops() {
+    let x = || Input::from("x");
+    let cases = [
+        (Op::ReluThreshold(x(), 0.5), "ReluThreshold(x, 0.5)"),
+        (Op::Pow(x(), 3.0), "Pow(x, 3)"),
+        (Op::SumAxis(x(), 1), "SumAxis(x, 1)"),
+        (Op::Reshape(x(), vec![2, 3]), "Reshape(x, [2, 3])"),
+        (Op::Transpose(x(), 0, 2), "Transpose(x, 0, 2)"),
+        (Op::SplitAt(x(), 4), "SplitAt(x, 4)"),
+        (Op::Dropout(x(), 0.25), "Dropout(x, 0.25)"),
+        (Op::Reciprocal(x()), "Reciprocal(x)"),
+        (Op::Sin(x()), "Sin(x)"),
+        (Op::Cos(x()), "Cos(x)"),
+        (Op::Gelu(x()), "Gelu(x)"),
+        (Op::LeakyRelu(x(), 0.01), "LeakyRelu(x, 0.01)"),
+        (Op::SquaredDiff(x(), x()), "SquaredDiff(x, x)"),
+        (Op::Stack(vec![x(), x()], 0), "Stack(x, x, 0)"),
+        (Op::Pad(x(), vec![(1, 2)], -1.0), "Pad(x, [(1, 2)], -1)"),
+        (
+            Op::Conv2d {
+                input: x(),
+                kernel: internal(0, 0),
+                stride: (2, 1),
+                padding: (0, 1),
+            },
+            "Conv2d(x, #0, (2, 1), (0, 1))",
+        ),
+        (
+            Op::MaxPool2d {
+                input: x(),
+                kernel: (2, 2),
+                stride: (2, 1),
+            },
+            "MaxPool2d(x, (2, 2), (2, 1))",
+        ),
+        (
+            Op::Embedding {
+                table: internal(0, 0),
+                indices: x(),
+            },
+            "Embedding(#0, x)",
+        ),
+        (
+            Op::BatchNorm {
+                input: x(),
+                gamma: internal(0, 0),
+                beta: internal(1, 0),
+            },
+            "BatchNorm(x, #0, #1)",
+        ),
+        (
+            Op::LayerNorm {
+                input: x(),
+                gamma: internal(0, 0),
+                beta: internal(1, 0),
+                eps: 0.001,
+            },
+            "LayerNorm(x, #0, #1, 0.001)",
+        ),
+        (Op::Clamp(x(), -1.0, 1.5), "Clamp(x, -1, 1.5)"),
+        (Op::Softmax(x(), 1), "Softmax(x, 1)"),
+        (Op::LogSoftmax(x(), 0), "LogSoftmax(x, 0)"),
+        (Op::Broadcast(x(), vec![4, 3]), "Broadcast(x, [4, 3])"),
+        (
+            Op::Concat(vec![x(), internal(1, 0), x()], 1),
+            "Concat(x, #1, x, 1)",
+        ),
+        (
+            Op::Slice(x(), vec![(0, 2), (1, 3)]),
+            "Slice(x, [(0, 2), (1, 3)])",
+        ),
+        (Op::Const(vec![2, 2], 1.5), "Const([2, 2], 1.5)"),
+        (Op::TrainConst(vec![], 0.0), "TrainConst([], 0)"),
+        (
+            Op::TrainInit(
+                vec![3],
+                InitKind::Normal {
+                    mean: 0.0,
+                    std: 1.0,
+                },
+            ),
+            "TrainInit([3], Normal { mean: 0.0, std: 1.0 })",
+        ),
+    ];
+    for (op, rendered) in &cases {
+        assert_eq!(op.to_string(), *rendered);
+    }
+}
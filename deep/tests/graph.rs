@@ -0,0 +1,503 @@
+use deep::*;
+
+#[test]
+fn topological_order_merged() {
+    let a = Tensor::from("a").squared().exp();
+    let b = Tensor::from("b").tanh();
+    let c = a - b;
+
+    // Merging shifted the right-hand ops after the left-hand ones.
+    let graph = c.graph();
+    assert_eq!(graph.topological_order(), Ok(vec![0, 1, 2, 3]));
+}
+
+#[test]
+fn topological_order_forward_reference() {
+    let mut graph = Graph::new();
+    let forward = Internal { node: 1, output: 0 };
+    graph.append_unchecked(Op::Square(Input::Internal(forward)));
+    graph.append_unchecked(Op::TrainConst(vec![], 1.0));
+    let error = graph.topological_order().unwrap_err();
+    assert_eq!(
+        error,
+        DeepError::Cycle {
+            node: 0,
+            input: forward
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "node 0 depends on node 1 which does not come before it"
+    );
+}
+
+#[test]
+fn validate_ok() {
+    let c = (Tensor::from("a").squared() + Tensor::from("b").exp()).sum();
+    assert_eq!(c.graph().validate(), Ok(()));
+}
+
+#[test]
+fn validate_node_out_of_range() {
+    let mut graph = Graph::new();
+    graph.append_unchecked(Op::TrainConst(vec![], 1.0));
+    graph.append_unchecked(Op::Square(Input::internal(5)));
+    assert_eq!(graph.validate(), Err(DeepError::DanglingNode(5)));
+}
+
+#[test]
+fn validate_output_out_of_range() {
+    let mut graph = Graph::new();
+    graph.append_unchecked(Op::TrainConst(vec![], 1.0));
+    let dangling = Internal { node: 0, output: 1 };
+    graph.append_unchecked(Op::Square(Input::Internal(dangling)));
+    assert_eq!(graph.validate(), Err(DeepError::DanglingOutput(dangling)));
+    assert_eq!(
+        graph.validate().unwrap_err().to_string(),
+        "output 1 of node 0 doesn't exist"
+    );
+}
+
+#[test]
+fn feed_keys() {
+    let c = Tensor::from("y") + (Tensor::from("x") * Tensor::from("y"));
+    let keys: Vec<String> = c.graph().feed_keys().into_iter().collect();
+    assert_eq!(keys, vec!["x".to_owned(), "y".to_owned()]);
+}
+
+#[test]
+fn to_dot() {
+    let c = (Tensor::from("a").squared() + Tensor::from("b")).exp();
+    let dot = c.graph().to_dot();
+
+    assert!(dot.starts_with("digraph {\n"));
+    assert!(dot.contains("    0 [label=\"Square\"];\n"));
+    assert!(dot.contains("    1 [label=\"Add\"];\n"));
+    assert!(dot.contains("    2 [label=\"Exp\"];\n"));
+    assert!(dot.contains("    \"feed_a\" [label=\"a\", shape=box];\n"));
+    assert!(dot.contains("    \"feed_a\" -> 0;\n"));
+    assert!(dot.contains("    \"feed_b\" -> 1;\n"));
+    // One edge for each internal dependency.
+    assert!(dot.contains("    0 -> 1;\n"));
+    assert!(dot.contains("    1 -> 2;\n"));
+    assert_eq!(dot.matches(" -> ").count(), 4);
+}
+
+#[test]
+fn replace_feed() {
+    let y = Tensor::concat(
+        vec![
+            Tensor::from("x").squared() + Tensor::from("x"),
+            Tensor::from("y"),
+            Tensor::from("x"),
+        ],
+        0,
+    );
+    let mut graph = y.graph().clone();
+    graph.replace_feed("x", "input_0");
+
+    assert_eq!(
+        graph.pretty(),
+        "%0 = Square(input_0)\n%1 = Add(#0, input_0)\n%2 = Concat(#1, y, input_0, 0)\n"
+    );
+    assert_eq!(
+        graph.feed_keys().into_iter().collect::<Vec<_>>(),
+        vec!["input_0", "y"]
+    );
+}
+
+#[test]
+fn pretty() {
+    let c = Tensor::from("a").squared() + Tensor::from("b").exp();
+    assert_eq!(
+        c.graph().pretty(),
+        "%0 = Square(a)\n%1 = Exp(b)\n%2 = Add(#0, #1)\n"
+    );
+    assert_eq!(Graph::new().pretty(), "");
+}
+
+#[test]
+fn fingerprint_structural() {
+    let build = || (Tensor::from("x") * Tensor::constant(vec![2], 0.5)).squared();
+    let graph = build().graph().clone();
+
+    // Separately built but identical graphs hash the same, and the hash doesn't change.
+    assert_eq!(graph.fingerprint(), build().graph().fingerprint());
+    assert_eq!(graph.fingerprint(), graph.clone().fingerprint());
+    assert_ne!(graph.fingerprint(), Graph::new().fingerprint());
+}
+
+#[test]
+fn fingerprint_changes() {
+    let constant = Input::Internal(Internal { node: 0, output: 0 });
+    let build = |constant: Op, product: Op| {
+        let mut graph = Graph::new();
+        graph.append(constant).unwrap();
+        graph.append(product).unwrap();
+        graph
+    };
+    let graph = (Tensor::from("x") * Tensor::constant(vec![2], 0.5))
+        .graph()
+        .clone();
+
+    let value = build(
+        Op::Const(vec![2], 0.25),
+        Op::Mul(Input::from("x"), constant.clone()),
+    );
+    let shape = build(
+        Op::Const(vec![3], 0.5),
+        Op::Mul(Input::from("x"), constant.clone()),
+    );
+    let feed = build(
+        Op::Const(vec![2], 0.5),
+        Op::Mul(Input::from("y"), constant.clone()),
+    );
+    let variant = build(
+        Op::Const(vec![2], 0.5),
+        Op::Add(Input::from("x"), constant.clone()),
+    );
+    let same = build(Op::Const(vec![2], 0.5), Op::Mul(Input::from("x"), constant));
+
+    assert_eq!(same.fingerprint(), graph.fingerprint());
+    for changed in &[value, shape, feed, variant] {
+        assert_ne!(changed.fingerprint(), graph.fingerprint());
+    }
+}
+
+#[test]
+fn node_count() {
+    let c = Tensor::from("a").squared() + Tensor::from("b").exp();
+    assert_eq!(c.graph().node_count(), 3);
+    assert_eq!(Graph::new().node_count(), 0);
+}
+
+#[test]
+fn input_count() {
+    let x = || Input::from("x");
+    let binary = [
+        Op::Add(x(), x()),
+        Op::Sub(x(), x()),
+        Op::Mul(x(), x()),
+        Op::Div(x(), x()),
+        Op::Max(x(), x()),
+        Op::Min(x(), x()),
+        Op::MatMul(x(), x()),
+    ];
+    let unary = [
+        Op::Square(x()),
+        Op::Neg(x()),
+        Op::Exp(x()),
+        Op::Log(x()),
+        Op::Sqrt(x()),
+        Op::Tanh(x()),
+        Op::Sigmoid(x()),
+        Op::Relu(x()),
+        Op::ReluThreshold(x(), 0.5),
+        Op::Pow(x(), 3.0),
+        Op::Sum(x()),
+        Op::Mean(x()),
+        Op::SumAxis(x(), 0),
+        Op::Reshape(x(), vec![1]),
+        Op::Transpose(x(), 0, 1),
+    ];
+    let nullary = [
+        Op::Const(vec![], 1.0),
+        Op::TrainConst(vec![], 1.0),
+        Op::TrainInit(vec![], InitKind::Xavier),
+    ];
+
+    assert!(binary.iter().all(|op| op.input_count() == 2));
+    assert!(unary.iter().all(|op| op.input_count() == 1));
+    assert!(nullary.iter().all(|op| op.input_count() == 0));
+}
+
+#[test]
+fn op_inputs() {
+    let a = Input::from("a");
+    let b = Input::Internal(Internal { node: 3, output: 0 });
+
+    assert_eq!(Op::Add(a.clone(), b.clone()).inputs(), vec![&a, &b]);
+    assert_eq!(Op::Pow(b.clone(), 2.0).inputs(), vec![&b]);
+    assert!(Op::TrainConst(vec![1], 0.0).inputs().is_empty());
+}
+
+#[test]
+fn dedup_square() {
+    let x = Tensor::from("x");
+    let c = x.squared() + x.squared();
+
+    let mut graph = c.graph().clone();
+    assert_eq!(graph.node_count(), 3);
+    let remap = graph.dedup();
+
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(remap[&0], 0);
+    assert_eq!(remap[&1], 0);
+    assert_eq!(remap[&2], 1);
+    let square = Input::Internal(Internal { node: 0, output: 0 });
+    assert_eq!(graph.ops()[1], Op::Add(square.clone(), square));
+}
+
+#[test]
+fn dedup_keeps_train_consts() {
+    let c = Tensor::train_const(vec![], 1.0) + Tensor::train_const(vec![], 1.0);
+
+    let mut graph = c.graph().clone();
+    graph.dedup();
+    assert_eq!(graph.node_count(), 3);
+}
+
+#[test]
+fn dedup_keeps_dropouts() {
+    let x = Tensor::from("x");
+    let y = x.dropout(0.5) + x.dropout(0.5);
+
+    // Each dropout draws its own mask, so they must stay separate nodes.
+    let mut graph = y.graph().clone();
+    graph.dedup();
+    assert_eq!(graph.node_count(), 3);
+    assert_eq!(graph, *y.graph());
+}
+
+#[test]
+fn prune_to_operand() {
+    let a = Tensor::from("a").squared();
+    let b = Tensor::from("b").exp().tanh();
+    let c = a.clone() + b;
+
+    let mut graph = c.graph().clone();
+    assert_eq!(graph.node_count(), 4);
+    let remap = graph.prune(a.input());
+
+    // Only the square of `a` is left.
+    assert_eq!(graph.node_count(), 1);
+    assert_eq!(graph.ops()[0], Op::Square(Input::from("a")));
+    assert_eq!(remap.len(), 1);
+    assert_eq!(remap[&0], 0);
+}
+
+#[test]
+fn prune_compacts() {
+    let c = Tensor::from("u").exp() - Tensor::from("x").squared().sum();
+
+    let mut graph = c.graph().clone();
+    let output = Input::Internal(Internal { node: 2, output: 0 });
+    let remap = graph.prune(&output);
+
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(remap[&1], 0);
+    assert_eq!(remap[&2], 1);
+    assert_eq!(
+        graph.ops()[1],
+        Op::Sum(Input::Internal(Internal { node: 0, output: 0 }))
+    );
+}
+
+#[test]
+fn remap_after_prune() {
+    let c = Tensor::from("u").exp() - Tensor::from("x").squared().sum();
+
+    let mut graph = c.graph().clone();
+    let mut output = Input::Internal(Internal { node: 2, output: 0 });
+    let remap = graph.prune(&output);
+
+    // The sum moved from node 2 to node 1 once the unused exp was removed.
+    output.remap(&remap);
+    assert_eq!(output, Input::Internal(Internal { node: 1, output: 0 }));
+    assert!(matches!(graph.ops()[1], Op::Sum(..)));
+
+    let mut feed = Input::from("x");
+    feed.remap(&remap);
+    assert_eq!(feed, Input::from("x"));
+}
+
+#[test]
+#[should_panic(expected = "node 0 was removed from the graph")]
+fn remap_removed_node() {
+    let mut graph = (Tensor::from("u").exp() - Tensor::from("x").squared())
+        .graph()
+        .clone();
+    let remap = graph.prune(&Input::Internal(Internal { node: 1, output: 0 }));
+
+    let mut exp = Internal { node: 0, output: 0 };
+    exp.remap(&remap);
+}
+
+#[test]
+fn fold_constants() {
+    let c = Tensor::scalar(2.0) + Tensor::scalar(3.0);
+    let mut graph = c.graph().clone();
+    let remap = graph.fold_constants();
+
+    // The add and the constants it consumed are replaced by the result.
+    assert_eq!(graph.ops().len(), 1);
+    assert_eq!(graph.ops(), vec![Op::Const(vec![], 5.0)]);
+    assert_eq!(remap[&2], 0);
+    assert!(!remap.contains_key(&0) && !remap.contains_key(&1));
+}
+
+#[test]
+fn fold_constants_chain() {
+    let folded = -(Tensor::scalar(1.0) - Tensor::scalar(4.0)).squared();
+    let trainable = Tensor::train_const(vec![], 1.0) + Tensor::scalar(2.0);
+    let fed = Tensor::from("x") + Tensor::scalar(2.0);
+    let vector = Tensor::constant(vec![2], 1.0) + Tensor::constant(vec![2], 4.0);
+
+    let mut graph = folded.graph().clone();
+    let remap = graph.fold_constants();
+    assert_eq!(graph.ops(), vec![Op::Const(vec![], -9.0)]);
+    assert_eq!(remap[&4], 0);
+
+    // Neither trainable constants, feeds nor constants which aren't scalars are folded.
+    for tensor in &[trainable, fed, vector] {
+        let mut graph = tensor.graph().clone();
+        graph.fold_constants();
+        assert_eq!(graph.ops(), tensor.graph().ops());
+    }
+}
+
+#[test]
+fn fold_constants_keeps_shared_constants() {
+    // The constant is still used by the add of the feed, so only the square of it is replaced.
+    let mut graph = Graph::new();
+    graph.append(Op::Const(vec![], 2.0)).unwrap();
+    graph.append(Op::Square(Input::internal(0))).unwrap();
+    graph
+        .append(Op::Add(Input::from("x"), Input::internal(0)))
+        .unwrap();
+    let remap = graph.fold_constants();
+    assert_eq!(graph.ops().len(), 3);
+    assert_eq!(graph.ops()[remap[&0]], Op::Const(vec![], 2.0));
+    assert_eq!(graph.ops()[remap[&1]], Op::Const(vec![], 4.0));
+}
+
+#[test]
+fn append_rejects_future_node() {
+    let mut graph = Graph::new();
+    let error = graph.append(Op::Square(Input::internal(1))).unwrap_err();
+    assert_eq!(error, DeepError::DanglingNode(1));
+    assert_eq!(error.to_string(), "node 1 doesn't exist");
+    assert_eq!(graph.node_count(), 0);
+
+    let square = graph.append(Op::Square(Input::from("x"))).unwrap();
+    let output = Input::Internal(Internal {
+        node: square,
+        output: 0,
+    });
+    assert_eq!(graph.append(Op::Neg(output)), Ok(1));
+}
+
+#[test]
+fn freeze_follows_nodes() {
+    let frozen = Tensor::train_const(vec![], 1.0);
+    frozen.freeze();
+    let y = (Tensor::train_const(vec![], 2.0) + frozen).sum();
+    let mut graph = y.graph().clone();
+    assert!(!graph.is_frozen(0));
+    assert!(graph.is_frozen(1));
+
+    // Dropping the first parameter moves the frozen one to node 0.
+    let output = Input::Internal(Internal { node: 1, output: 0 });
+    graph.prune(&output);
+    assert!(graph.is_frozen(0));
+
+    graph.unfreeze(0);
+    assert!(!graph.is_frozen(0));
+}
+
+#[test]
+fn fold_constants_reciprocal() {
+    let y = Tensor::scalar(4.0).recip();
+    let mut graph = y.graph().clone();
+    graph.fold_constants();
+    assert_eq!(graph.ops(), vec![Op::Const(vec![], 0.25)]);
+}
+
+#[test]
+fn accept_visits_every_op() {
+    #[derive(Default)]
+    struct Counter(Vec<(usize, OpTy)>);
+
+    impl GraphVisitor for Counter {
+        fn visit_op(&mut self, index: usize, op: &Op) {
+            self.0.push((index, OpTy::from(op)));
+        }
+    }
+
+    let output = (Tensor::from("x").squared() + Tensor::from("y")).exp();
+    let graph = output.graph();
+    let mut counter = Counter::default();
+    graph.accept(&mut counter);
+    assert_eq!(
+        counter.0,
+        vec![(0, OpTy::Square), (1, OpTy::Add), (2, OpTy::Exp)]
+    );
+}
+
+#[test]
+fn fuse_keeps_shared_sub() {
+    let diff = Tensor::from("a") - Tensor::from("b");
+    let y = diff.squared() + diff.clone();
+    let mut graph = y.graph().clone();
+    graph.fuse();
+    assert_eq!(graph, *y.graph());
+}
+
+#[test]
+fn is_acyclic_cycle() {
+    let input = |node| Input::Internal(Internal { node, output: 0 });
+    let mut graph = Graph::new();
+    graph.append_unchecked(Op::Square(input(2)));
+    graph.append_unchecked(Op::Exp(input(0)));
+    graph.append_unchecked(Op::Neg(input(1)));
+    assert!(!graph.is_acyclic());
+
+    // A forward reference alone isn't a cycle, although it breaks the topological order.
+    graph.map_ops(|node, op| {
+        if node == 2 {
+            *op = Op::Const(vec![], 1.0);
+        }
+    });
+    assert!(graph.is_acyclic());
+    assert!(graph.topological_order().is_err());
+
+    let merged = Tensor::from("a").squared().exp() + Tensor::from("b").tanh();
+    assert!(merged.graph().is_acyclic());
+}
+
+#[test]
+fn input_internal_constructors() {
+    assert_eq!(
+        Input::internal(3),
+        Input::Internal(Internal { node: 3, output: 0 })
+    );
+    assert_eq!(
+        Input::internal_out(3, 1),
+        Input::Internal(Internal { node: 3, output: 1 })
+    );
+    assert_eq!(Tensor::scalar(1.0).input(), &Input::internal(0));
+}
+
+#[test]
+fn graph_equality() {
+    let y = (Tensor::from("x") * Tensor::scalar(2.0)).exp();
+    let mut graph = y.graph().clone();
+    assert_eq!(graph, *y.graph());
+
+    graph.append(Op::Neg(Input::internal(2))).unwrap();
+    assert_ne!(graph, *y.graph());
+
+    let mut frozen = y.graph().clone();
+    frozen.freeze(0);
+    assert_ne!(frozen, *y.graph());
+}
+
+#[test]
+fn fingerprint_pinned() {
+    let graph = (Tensor::from("x") * Tensor::constant(vec![2], 0.5))
+        .graph()
+        .clone();
+
+    // Sizes are hashed as little-endian `u64`, so the value is the same on every platform.
+    assert_eq!(graph.fingerprint(), 6_316_559_735_703_851_181);
+}
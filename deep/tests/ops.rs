@@ -0,0 +1,774 @@
+use deep::*;
+
+#[test]
+fn mul_graph() {
+    let c = Tensor::from("a") * Tensor::from("b");
+
+    // Both operands are feeds, so the only op is the multiply itself.
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(
+        &graph.ops()[0],
+        Op::Mul(Input::Feed(a), Input::Feed(b)) if a == "a" && b == "b"
+    ));
+    assert!(matches!(
+        c.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn div_graph() {
+    let a = Tensor::from("a").squared();
+    let b = Tensor::from("b").squared();
+    let c = a / b;
+
+    // The numerator's graph comes first and the denominator's is appended after it.
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(&graph.ops()[0], Op::Square(Input::Feed(a)) if a == "a"));
+    assert!(matches!(&graph.ops()[1], Op::Square(Input::Feed(b)) if b == "b"));
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Div(
+            Input::Internal(Internal { node: 0, output: 0 }),
+            Input::Internal(Internal { node: 1, output: 0 })
+        )
+    ));
+}
+
+#[test]
+fn neg_graph() {
+    let x = Tensor::from("a") - Tensor::from("b");
+    let y = -x;
+
+    // Only the negation is appended after the subtraction.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::Neg(Input::Internal(Internal { node: 0, output: 0 }))
+    ));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 1, output: 0 })
+    ));
+}
+
+#[test]
+fn exp_graph() {
+    let y = Tensor::from("x").exp();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Exp(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn log_graph() {
+    let y = Tensor::from("x").ln();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Log(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn sqrt_graph() {
+    let y = Tensor::from("x").sqrt();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Sqrt(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn tanh_graph() {
+    let y = Tensor::from("x").tanh();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Tanh(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn sigmoid_graph() {
+    let y = Tensor::from("x").sigmoid();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Sigmoid(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn relu_graph() {
+    let y = Tensor::from("x").relu();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Relu(Input::Feed(x)) if x == "x"));
+}
+
+#[test]
+fn relu_threshold_merge() {
+    let y = Tensor::from("x").squared().relu_threshold(0.5);
+
+    // Merge the relu graph after a graph which already has one op.
+    let mut graph = Tensor::from("z").squared().graph().clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::ReluThreshold(Input::Internal(Internal { node: 1, output: 0 }), t) if *t == 0.5
+    ));
+}
+
+#[test]
+fn pow_merge_input() {
+    let y = Tensor::from("x").powf(3.0);
+
+    // Merge the pow graph after a graph which already has two ops.
+    let mut graph = (Tensor::from("a") + Tensor::from("b"))
+        .squared()
+        .graph()
+        .clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Pow(Input::Feed(x), e) if x == "x" && *e == 3.0
+    ));
+}
+
+#[test]
+fn max_graph() {
+    let a = Tensor::from("a").exp();
+    let b = Tensor::from("b").exp();
+    let c = a.maximum(b);
+
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(&graph.ops()[1], Op::Exp(Input::Feed(b)) if b == "b"));
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Max(
+            Input::Internal(Internal { node: 0, output: 0 }),
+            Input::Internal(Internal { node: 1, output: 0 })
+        )
+    ));
+}
+
+#[test]
+fn min_graph() {
+    let a = Tensor::from("a").squared().exp();
+    let b = Tensor::from("b").tanh();
+    let c = a.minimum(b);
+
+    // The right-hand graph is shifted past the two ops of the left-hand graph.
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 4);
+    assert!(matches!(&graph.ops()[2], Op::Tanh(Input::Feed(b)) if b == "b"));
+    assert!(matches!(
+        &graph.ops()[3],
+        Op::Min(
+            Input::Internal(Internal { node: 1, output: 0 }),
+            Input::Internal(Internal { node: 2, output: 0 })
+        )
+    ));
+}
+
+#[test]
+fn sum_graph() {
+    let loss = (Tensor::from("pred") - Tensor::from("target"))
+        .squared()
+        .sum();
+
+    let graph = loss.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Sum(Input::Internal(Internal { node: 1, output: 0 }))
+    ));
+    assert!(matches!(
+        loss.input(),
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+}
+
+#[test]
+fn mean_graph() {
+    let y = Tensor::from("x").mean();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Mean(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn sum_axis_merge() {
+    let y = Tensor::from("x").squared().sum_axis(1);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::SumAxis(Input::Internal(Internal { node: 1, output: 0 }), 1)
+    ));
+}
+
+#[test]
+fn reshape_merge() {
+    let y = Tensor::from("x").reshape(vec![2, 3]);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::Reshape(Input::Feed(x), shape) if x == "x" && shape == &[2, 3]
+    ));
+}
+
+#[test]
+fn transpose_merge_input() {
+    let y = Tensor::from("x").transpose(0, 2);
+    assert_eq!(y.graph().ops().len(), 1);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 1, output: 0 })
+    ));
+    assert!(matches!(&graph.ops()[1], Op::Transpose(Input::Feed(x), 0, 2) if x == "x"));
+}
+
+#[test]
+fn matmul_operand_order() {
+    let x = Tensor::from("x").tanh();
+    let w = Tensor::from("w").squared();
+    let y = x.matmul(w);
+
+    // Like Sub, the left operand stays first and the right operand is merged after it.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert!(matches!(&graph.ops()[1], Op::Square(Input::Feed(w)) if w == "w"));
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::MatMul(
+            Input::Internal(Internal { node: 0, output: 0 }),
+            Input::Internal(Internal { node: 1, output: 0 })
+        )
+    ));
+}
+
+#[test]
+fn constant_graph() {
+    let c = Tensor::constant(vec![2, 2], 1.5);
+
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Const(shape, v) if shape == &[2, 2] && *v == 1.5));
+    assert!(matches!(
+        c.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn scalar_graph() {
+    let c = Tensor::scalar(1.0);
+
+    let graph = c.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Const(shape, v) if shape.is_empty() && *v == 1.0));
+}
+
+#[test]
+fn split_at_outputs() {
+    let (head, tail) = Tensor::from("x").exp().split_at(2);
+
+    // Both halves come from the same op, only the output index differs.
+    assert!(head.shares_graph(&tail));
+    assert_eq!(tail.graph().ops().len(), 2);
+    assert!(matches!(
+        &tail.graph().ops()[1],
+        Op::SplitAt(Input::Internal(Internal { node: 0, output: 0 }), 2)
+    ));
+    assert!(matches!(
+        head.input(),
+        Input::Internal(Internal { node: 1, output: 0 })
+    ));
+    assert!(matches!(
+        tail.input(),
+        Input::Internal(Internal { node: 1, output: 1 })
+    ));
+    assert!(tail.graph().validate().is_ok());
+}
+
+#[test]
+fn select_output_merge() {
+    let tail = Tensor::from("x").split_at(1).0.output(1);
+    let y = Tensor::from("z").squared() + tail;
+
+    // The selected output survives being shifted by the merge.
+    let graph = y.graph();
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Add(
+            Input::Internal(Internal { node: 0, output: 0 }),
+            Input::Internal(Internal { node: 1, output: 1 })
+        )
+    ));
+}
+
+#[test]
+fn clamp_merge_input() {
+    let y = Tensor::from("x").tanh().clamp(-0.5, 0.25);
+    assert_eq!(y.graph().ops().len(), 2);
+
+    // Merge the clamp graph after a graph which already has one op.
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert!(matches!(
+        &graph.ops()[2],
+        Op::Clamp(Input::Internal(Internal { node: 1, output: 0 }), min, max)
+            if *min == -0.5 && *max == 0.25
+    ));
+}
+
+#[test]
+fn abs_graph() {
+    let x = Tensor::from("a") - Tensor::from("b");
+    let y = x.abs();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::Abs(Input::Internal(Internal { node: 0, output: 0 }))
+    ));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 1, output: 0 })
+    ));
+}
+
+#[test]
+fn softmax_merge() {
+    let y = Tensor::from("x").matmul(Tensor::from("w")).softmax(1);
+    assert_eq!(y.graph().ops().len(), 2);
+
+    // Merge the softmax graph after a graph which already has two ops.
+    let mut graph = (Tensor::from("a") + Tensor::from("b"))
+        .squared()
+        .graph()
+        .clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 4);
+    assert!(matches!(
+        &graph.ops()[3],
+        Op::Softmax(Input::Internal(Internal { node: 2, output: 0 }), 1)
+    ));
+}
+
+#[test]
+fn log_softmax_merge() {
+    let y = Tensor::from("logits").log_softmax(1);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::LogSoftmax(Input::Feed(logits), 1) if logits == "logits"
+    ));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn broadcast_merge() {
+    let y = Tensor::from("bias").broadcast_to(vec![4, 3]);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    graph.merge(y.graph().clone());
+
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::Broadcast(Input::Feed(bias), shape) if bias == "bias" && shape == &[4, 3]
+    ));
+}
+
+#[test]
+fn concat_three_operands() {
+    let a = Tensor::from("a").exp();
+    let b = Tensor::from("b").squared().tanh();
+    let y = Tensor::concat(vec![a, Tensor::from("c"), b], 1);
+
+    // Each operand's graph is shifted past everything merged before it.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 4);
+    assert_eq!(
+        graph.ops()[3],
+        Op::Concat(
+            vec![
+                Input::Internal(Internal { node: 0, output: 0 }),
+                Input::from("c"),
+                Input::Internal(Internal { node: 2, output: 0 }),
+            ],
+            1
+        )
+    );
+    assert_eq!(graph.ops()[3].input_count(), 3);
+
+    // Merging the whole graph shifts all three inputs.
+    let mut shifted = Tensor::from("z").exp().graph().clone();
+    shifted.merge(graph.clone());
+    assert_eq!(
+        shifted.ops()[4],
+        Op::Concat(
+            vec![
+                Input::Internal(Internal { node: 1, output: 0 }),
+                Input::from("c"),
+                Input::Internal(Internal { node: 3, output: 0 }),
+            ],
+            1
+        )
+    );
+}
+
+#[test]
+fn concat_shared_graph() {
+    let a = Tensor::from("a").exp();
+    let y = Tensor::concat(vec![a.clone(), a.squared(), a], 0);
+
+    // All the operands already live in the same graph, so nothing is merged.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert_eq!(
+        graph.ops()[2].inputs(),
+        vec![
+            &Input::Internal(Internal { node: 0, output: 0 }),
+            &Input::Internal(Internal { node: 1, output: 0 }),
+            &Input::Internal(Internal { node: 0, output: 0 }),
+        ]
+    );
+}
+
+#[test]
+fn slice_merge_input() {
+    let y = Tensor::from("x").squared().slice(vec![(0, 2), (1, 4)]);
+    assert_eq!(y.graph().ops().len(), 2);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[2],
+        Op::Slice(
+            Input::Internal(Internal { node: 1, output: 0 }),
+            vec![(0, 2), (1, 4)]
+        )
+    );
+}
+
+#[test]
+fn dropout() {
+    let y = Tensor::from("x").squared().dropout(0.5);
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert_eq!(
+        graph.ops()[1],
+        Op::Dropout(Input::Internal(Internal { node: 0, output: 0 }), 0.5)
+    );
+}
+
+#[test]
+fn reciprocal_graph() {
+    let y = Tensor::from("x").recip();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Reciprocal(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn sin_graph() {
+    let y = Tensor::from("x").sin();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Sin(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn cos_graph() {
+    let y = Tensor::from("x").cos();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 1);
+    assert!(matches!(&graph.ops()[0], Op::Cos(Input::Feed(x)) if x == "x"));
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn pad_merge_input() {
+    let y = Tensor::from("x").squared().pad(vec![(1, 1), (0, 2)], 0.5);
+    assert_eq!(y.graph().ops().len(), 2);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[2],
+        Op::Pad(
+            Input::Internal(Internal { node: 1, output: 0 }),
+            vec![(1, 1), (0, 2)],
+            0.5
+        )
+    );
+}
+
+#[test]
+fn conv2d_merge_input() {
+    let y = Tensor::from("x")
+        .squared()
+        .conv2d(Tensor::from("k").exp(), (2, 2), (1, 0));
+    let graph = y.graph().clone();
+    assert_eq!(graph.ops().len(), 3);
+
+    let mut merged = Tensor::from("z").exp().graph().clone();
+    let input = merged.merge_input(graph, y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 3, output: 0 })
+    ));
+    assert_eq!(
+        merged.ops()[3],
+        Op::Conv2d {
+            input: Input::Internal(Internal { node: 1, output: 0 }),
+            kernel: Input::Internal(Internal { node: 2, output: 0 }),
+            stride: (2, 2),
+            padding: (1, 0),
+        }
+    );
+    assert_eq!(merged.ops()[3].input_count(), 2);
+}
+
+#[test]
+fn max_pool2d_merge_input() {
+    let y = Tensor::from("x").relu().max_pool2d((2, 2), (2, 1));
+    assert_eq!(y.graph().ops().len(), 2);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[2],
+        Op::MaxPool2d {
+            input: Input::Internal(Internal { node: 1, output: 0 }),
+            kernel: (2, 2),
+            stride: (2, 1),
+        }
+    );
+}
+
+#[test]
+fn embedding_merge_input() {
+    let table = Tensor::train_const(vec![10, 4], 0.0);
+    let y = Tensor::embedding(table, Tensor::from("tokens").relu());
+    let graph = y.graph().clone();
+    assert_eq!(graph.ops().len(), 3);
+
+    let mut merged = Tensor::from("z").exp().graph().clone();
+    let input = merged.merge_input(graph, y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 3, output: 0 })
+    ));
+    assert_eq!(
+        merged.ops()[3],
+        Op::Embedding {
+            table: Input::Internal(Internal { node: 1, output: 0 }),
+            indices: Input::Internal(Internal { node: 2, output: 0 }),
+        }
+    );
+}
+
+#[test]
+fn batch_norm_merge_input() {
+    let y = Tensor::from("x").batch_norm(
+        Tensor::train_const(vec![3], 1.0),
+        Tensor::train_const(vec![3], 0.0),
+    );
+    assert_eq!(y.graph().ops().len(), 3);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 3, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[3],
+        Op::BatchNorm {
+            input: Input::from("x"),
+            gamma: Input::Internal(Internal { node: 1, output: 0 }),
+            beta: Input::Internal(Internal { node: 2, output: 0 }),
+        }
+    );
+    assert_eq!(graph.validate(), Ok(()));
+}
+
+#[test]
+fn layer_norm_merge_input() {
+    let y = Tensor::from("x").squared().layer_norm(
+        Tensor::train_const(vec![8], 1.0),
+        Tensor::train_const(vec![8], 0.0),
+        1e-5,
+    );
+    assert_eq!(y.graph().ops().len(), 4);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 4, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[4],
+        Op::LayerNorm {
+            input: Input::Internal(Internal { node: 1, output: 0 }),
+            gamma: Input::Internal(Internal { node: 2, output: 0 }),
+            beta: Input::Internal(Internal { node: 3, output: 0 }),
+            eps: 1e-5,
+        }
+    );
+}
+
+#[test]
+fn gelu_graph() {
+    let y = Tensor::from("x").squared().gelu();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert_eq!(
+        graph.ops()[1],
+        Op::Gelu(Input::Internal(Internal { node: 0, output: 0 }))
+    );
+    assert!(matches!(
+        y.input(),
+        Input::Internal(Internal { node: 1, output: 0 })
+    ));
+}
+
+#[test]
+fn leaky_relu_merge_input() {
+    let y = Tensor::from("x").matmul(Tensor::from("w")).leaky_relu(0.2);
+    assert_eq!(y.graph().ops().len(), 2);
+
+    let mut graph = Tensor::from("z").exp().graph().clone();
+    let input = graph.merge_input(y.graph().clone(), y.input().clone());
+
+    assert!(matches!(
+        input,
+        Input::Internal(Internal { node: 2, output: 0 })
+    ));
+    assert_eq!(
+        graph.ops()[2],
+        Op::LeakyRelu(Input::Internal(Internal { node: 1, output: 0 }), 0.2)
+    );
+}
+
+#[test]
+fn stack_two_operands() {
+    let a = Tensor::from("a").exp();
+    let b = Tensor::from("b").squared();
+    let y = Tensor::stack(vec![a, b], 1);
+
+    // The second operand's graph is shifted past the first one and the new axis is kept.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 3);
+    assert_eq!(
+        graph.ops()[2],
+        Op::Stack(vec![Input::internal(0), Input::internal(1)], 1)
+    );
+
+    let mut shifted = Tensor::from("z").exp().graph().clone();
+    shifted.merge(graph.clone());
+    assert_eq!(
+        shifted.ops()[3],
+        Op::Stack(vec![Input::internal(1), Input::internal(2)], 1)
+    );
+}
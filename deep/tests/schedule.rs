@@ -0,0 +1,35 @@
+use deep::*;
+
+#[test]
+fn step_decay_halves() {
+    let schedule = StepDecay {
+        base: 0.8,
+        gamma: 0.5,
+        step_size: 3,
+    };
+    let rates: Vec<f32> = (0..7).map(|step| schedule.lr(step)).collect();
+    assert_eq!(rates, vec![0.8, 0.8, 0.8, 0.4, 0.4, 0.4, 0.2]);
+}
+
+#[test]
+fn step_decay_zero_step_size() {
+    let schedule = StepDecay {
+        base: 0.8,
+        gamma: 0.5,
+        step_size: 0,
+    };
+    assert_eq!(schedule.lr(0), 0.8);
+    assert_eq!(schedule.lr(100), 0.8);
+}
+
+#[test]
+fn constant_and_exponential() {
+    assert_eq!(Constant(0.1).lr(1000), 0.1);
+
+    let schedule = ExponentialDecay {
+        base: 1.0,
+        gamma: 0.5,
+    };
+    assert_eq!(schedule.lr(0), 1.0);
+    assert_eq!(schedule.lr(3), 0.125);
+}
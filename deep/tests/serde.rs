@@ -0,0 +1,18 @@
+#![cfg(feature = "serde")]
+
+use deep::*;
+
+#[test]
+fn graph_json_round_trip() {
+    let c = Tensor::from("x") + Tensor::train_const(vec![2], 0.5).squared();
+    let graph = c.graph().clone();
+    assert_eq!(graph.ops().len(), 3);
+
+    let json = serde_json::to_string(&graph).expect("unable to serialize graph");
+    let deserialized: Graph = serde_json::from_str(&json).expect("unable to deserialize graph");
+
+    assert_eq!(
+        format!("{:?}", deserialized.ops()),
+        format!("{:?}", graph.ops())
+    );
+}
@@ -0,0 +1,213 @@
+use deep::shapes::{infer_output_shapes, infer_shapes};
+use deep::*;
+use std::collections::HashMap;
+
+#[test]
+fn infer_valid_graph() {
+    let w = Tensor::train_const(vec![3, 2], 0.5);
+    let b = Tensor::train_const(vec![2], 0.0);
+    let loss = (Tensor::from("x").matmul(w) + b).squared().sum_axis(1);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![4, 3]);
+
+    // The bias is broadcast across the rows of the product.
+    let shapes = infer_shapes(&loss.graph(), &feed_shapes).unwrap();
+    assert_eq!(
+        shapes,
+        vec![
+            vec![3, 2],
+            vec![4, 2],
+            vec![2],
+            vec![4, 2],
+            vec![4, 2],
+            vec![4]
+        ]
+    );
+}
+
+#[test]
+fn infer_mismatched_add() {
+    let y = Tensor::from("a").squared() + Tensor::constant(vec![3], 1.0);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("a".to_string(), vec![2, 2]);
+
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch {
+            node: 2,
+            shapes: vec![vec![2, 2], vec![3]]
+        })
+    );
+}
+
+#[test]
+fn infer_missing_feed() {
+    let y = -Tensor::from("a");
+
+    assert_eq!(
+        infer_shapes(&y.graph(), &HashMap::new()),
+        Err(DeepError::UnknownFeed("a".to_string()))
+    );
+}
+
+#[test]
+fn infer_split_at() {
+    let (head, tail) = Tensor::from("x").split_at(1);
+    let y = tail.squared();
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![3, 2]);
+
+    let shapes = infer_output_shapes(&y.graph(), &feed_shapes).unwrap();
+    assert_eq!(shapes[0], vec![vec![1, 2], vec![2, 2]]);
+    assert_eq!(shapes[1], vec![vec![2, 2]]);
+    assert!(head.shares_graph(&y));
+}
+
+#[test]
+fn infer_broadcast() {
+    let y = Tensor::from("bias").broadcast_to(vec![4, 3]);
+    let bad = Tensor::from("bias").broadcast_to(vec![4, 2]);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("bias".to_string(), vec![3]);
+
+    assert_eq!(infer_shapes(&y.graph(), &feed_shapes), Ok(vec![vec![4, 3]]));
+    assert!(matches!(
+        infer_shapes(&bad.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch { node: 0, .. })
+    ));
+}
+
+#[test]
+fn infer_concat() {
+    let y = Tensor::concat(vec![Tensor::from("a"), Tensor::from("b")], 1);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("a".to_string(), vec![2, 3]);
+    feed_shapes.insert("b".to_string(), vec![2, 1]);
+    assert_eq!(infer_shapes(&y.graph(), &feed_shapes), Ok(vec![vec![2, 4]]));
+
+    feed_shapes.insert("b".to_string(), vec![3, 1]);
+    assert!(matches!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch { node: 0, .. })
+    ));
+}
+
+#[test]
+fn infer_axis_out_of_range() {
+    let y = Tensor::from("x").sum_axis(2);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![2, 3]);
+
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch {
+            node: 0,
+            shapes: vec![vec![2, 3]]
+        })
+    );
+}
+
+#[test]
+fn infer_pad() {
+    let y = Tensor::from("x").pad(vec![(1, 2), (0, 3)], 0.0);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![2, 3]);
+    assert_eq!(infer_shapes(&y.graph(), &feed_shapes), Ok(vec![vec![5, 6]]));
+}
+
+#[test]
+fn infer_conv2d() {
+    let y = Tensor::from("x").conv2d(Tensor::from("k"), (2, 1), (1, 0));
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![4, 3, 8, 6]);
+    feed_shapes.insert("k".to_string(), vec![5, 3, 3, 3]);
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Ok(vec![vec![4, 5, 4, 4]])
+    );
+
+    feed_shapes.insert("k".to_string(), vec![5, 2, 3, 3]);
+    assert!(matches!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch { node: 0, .. })
+    ));
+}
+
+#[test]
+fn infer_max_pool2d() {
+    let y = Tensor::from("x").max_pool2d((2, 3), (2, 1));
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("x".to_string(), vec![1, 3, 6, 5]);
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Ok(vec![vec![1, 3, 3, 3]])
+    );
+}
+
+#[test]
+fn infer_embedding() {
+    let y = Tensor::embedding(
+        Tensor::train_const(vec![10, 4], 0.0),
+        Tensor::from("tokens"),
+    );
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("tokens".to_string(), vec![2, 7]);
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Ok(vec![vec![10, 4], vec![2, 7, 4]])
+    );
+}
+
+#[test]
+fn infer_stack() {
+    let y = Tensor::stack(vec![Tensor::from("a"), Tensor::from("b")], 1);
+
+    let mut feed_shapes = HashMap::new();
+    feed_shapes.insert("a".to_string(), vec![2, 3]);
+    feed_shapes.insert("b".to_string(), vec![2, 3]);
+    assert_eq!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Ok(vec![vec![2, 2, 3]])
+    );
+
+    feed_shapes.insert("b".to_string(), vec![2, 1]);
+    assert!(matches!(
+        infer_shapes(&y.graph(), &feed_shapes),
+        Err(DeepError::ShapeMismatch { node: 0, .. })
+    ));
+}
+
+#[test]
+fn infer_dangling_node() {
+    let mut graph = Graph::new();
+    graph.append_unchecked(Op::Const(vec![2], 1.0));
+    graph.append_unchecked(Op::Square(Input::internal_out(0, 1)));
+    assert_eq!(
+        infer_shapes(&graph, &HashMap::new()),
+        Err(DeepError::DanglingOutput(Internal { node: 0, output: 1 }))
+    );
+}
+
+#[test]
+fn infer_forward_reference() {
+    let mut graph = Graph::new();
+    graph.append_unchecked(Op::Square(Input::internal(1)));
+    graph.append_unchecked(Op::Const(vec![2], 1.0));
+    assert_eq!(
+        infer_shapes(&graph, &HashMap::new()),
+        Err(DeepError::Cycle {
+            node: 0,
+            input: Internal { node: 1, output: 0 },
+        })
+    );
+}
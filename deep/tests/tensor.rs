@@ -0,0 +1,201 @@
+use deep::*;
+
+#[test]
+fn add_sub_by_reference() {
+    let a = Tensor::from("a");
+    let b = Tensor::from("b").squared();
+
+    let sum = &a + &b;
+    // `a` and `b` were not moved, so they can be used again.
+    let diff = &a - &b;
+
+    assert!(matches!(
+        &sum.graph().ops()[1],
+        Op::Add(Input::Feed(a), Input::Internal(Internal { node: 0, output: 0 })) if a == "a"
+    ));
+    assert!(matches!(diff.graph().ops().last(), Some(Op::Sub(Input::Feed(a), _)) if a == "a"));
+    assert!(matches!(a.input(), Input::Feed(a) if a == "a"));
+}
+
+#[test]
+fn add_same_tensor_by_reference() {
+    let a = Tensor::from("a").squared();
+    let doubled = &a + &a;
+
+    // Both operands already live in the same graph, so nothing is merged.
+    let graph = doubled.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert!(matches!(
+        &graph.ops()[1],
+        Op::Add(
+            Input::Internal(Internal { node: 0, output: 0 }),
+            Input::Internal(Internal { node: 0, output: 0 })
+        )
+    ));
+}
+
+#[test]
+fn clone_shares_graph() {
+    let a = Tensor::from("a").squared();
+    let b = a.clone();
+    assert!(a.shares_graph(&b));
+
+    // Appending through the clone is visible through the original.
+    let _ = b.exp();
+    assert_eq!(a.graph().ops().len(), 2);
+}
+
+#[test]
+fn detach_copies_graph() {
+    let a = Tensor::from("a").squared();
+    let b = a.detach();
+    assert!(!a.shares_graph(&b));
+
+    // Appending to the original doesn't touch the detached copy.
+    let _ = a.exp();
+    assert_eq!(a.graph().ops().len(), 2);
+    assert_eq!(b.graph().ops().len(), 1);
+}
+
+#[test]
+fn from_f64_scalar() {
+    let c = Tensor::from(2.5f64);
+
+    let graph = c.graph();
+    assert_eq!(graph.ops(), vec![Op::Const(vec![], 2.5)]);
+    assert!(matches!(
+        c.input(),
+        Input::Internal(Internal { node: 0, output: 0 })
+    ));
+}
+
+#[test]
+fn from_i32_scalar() {
+    let y = Tensor::from("x") - Tensor::from(3);
+
+    // The literal becomes a scalar constant merged after the feed.
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert_eq!(graph.ops()[0], Op::Const(vec![], 3.0));
+}
+
+#[test]
+fn scalar_on_the_right() {
+    let x = || Tensor::from("x");
+    let scalar = Input::Internal(Internal { node: 0, output: 0 });
+
+    // The feed graph is empty, so the scalar constant ends up as node 0.
+    for (y, op) in [
+        (x() + 2.0, Op::Add(Input::from("x"), scalar.clone())),
+        (x() - 2.0, Op::Sub(Input::from("x"), scalar.clone())),
+        (x() * 2.0, Op::Mul(Input::from("x"), scalar)),
+    ] {
+        assert_eq!(y.graph().ops(), vec![Op::Const(vec![], 2.0), op]);
+    }
+}
+
+#[test]
+fn scalar_on_the_left() {
+    let x = Tensor::from("x").squared();
+    let square = Input::Internal(Internal { node: 1, output: 0 });
+    let scalar = Input::Internal(Internal { node: 0, output: 0 });
+
+    // The scalar stays the first operand, so `1.0 - x` isn't turned into `x - 1.0`.
+    for (y, op) in [
+        (1.0 + x.detach(), Op::Add(scalar.clone(), square.clone())),
+        (1.0 - x.detach(), Op::Sub(scalar.clone(), square.clone())),
+        (1.0 * x, Op::Mul(scalar, square)),
+    ] {
+        assert_eq!(y.graph().ops()[2], op);
+    }
+}
+
+#[test]
+fn add_assign_loop() {
+    let mut total = Tensor::scalar(0.0);
+    for name in &["a", "b", "c"] {
+        total += Tensor::from(*name).squared();
+    }
+
+    // The constant, then a square and an add for every term.
+    let graph = total.graph();
+    assert_eq!(graph.ops().len(), 7);
+    assert_eq!(
+        graph.ops()[6],
+        Op::Add(
+            Input::Internal(Internal { node: 4, output: 0 }),
+            Input::Internal(Internal { node: 5, output: 0 })
+        )
+    );
+    assert_eq!(
+        total.input(),
+        &Input::Internal(Internal { node: 6, output: 0 })
+    );
+}
+
+#[test]
+fn sub_assign() {
+    let mut y = Tensor::from("x");
+    y -= Tensor::from("b").exp();
+
+    let graph = y.graph();
+    assert_eq!(graph.ops().len(), 2);
+    assert_eq!(
+        graph.ops()[1],
+        Op::Sub(
+            Input::from("x"),
+            Input::Internal(Internal { node: 0, output: 0 })
+        )
+    );
+}
+
+#[test]
+fn sum_of_four() {
+    let terms = ["a", "b", "c", "d"]
+        .iter()
+        .map(|&name| Tensor::from(name).exp());
+    let total = Tensor::sum_of(terms).unwrap();
+
+    // Every term's exp is merged before the add which consumes it.
+    let graph = total.graph();
+    let adds = graph
+        .ops()
+        .iter()
+        .filter(|op| matches!(op, Op::Add(..)))
+        .count();
+    assert_eq!(adds, 3);
+    assert_eq!(graph.ops().len(), 7);
+    assert!(matches!(&graph.ops()[5], Op::Exp(Input::Feed(d)) if d == "d"));
+
+    assert!(Tensor::sum_of(Vec::new()).is_none());
+}
+
+#[test]
+fn mse_with_l2_regularization() {
+    let w = Tensor::train_const(vec![2], 1.0);
+    let mse = Tensor::mse(w.clone() * Tensor::from("x"), Tensor::from("y"));
+    let mse_nodes = mse.graph().ops().len();
+    let loss = mse + Tensor::l2_regularization(&[w], 0.01);
+
+    // Square, sum and scale the parameter, then add the penalty to the loss.
+    let graph = loss.graph();
+    let w = Input::Internal(Internal { node: 0, output: 0 });
+    assert_eq!(graph.ops().len(), mse_nodes + 5);
+    assert_eq!(graph.ops()[mse_nodes], Op::Square(w));
+    assert!(matches!(graph.ops()[mse_nodes + 1], Op::Sum(..)));
+    assert_eq!(graph.ops()[mse_nodes + 2], Op::Const(vec![], 0.01));
+    assert!(matches!(graph.ops()[mse_nodes + 4], Op::Add(..)));
+}
+
+#[test]
+fn feed_from_string() {
+    let layer = 2;
+    let name = format!("input_{}", layer);
+    assert_eq!(Input::from(name.clone()), Input::from("input_2"));
+
+    let x = Tensor::from(name).squared();
+    assert_eq!(
+        x.graph().feed_keys().into_iter().collect::<Vec<_>>(),
+        vec!["input_2"]
+    );
+}